@@ -0,0 +1,6 @@
+//! # DAO Bootloader Contract
+//!
+//! A bootloader owned by a DAO. Anyone can call `redeploy` with a `code_hash`,
+//! but the contract is only redeployed if the owner's `is_redeploy_approved` view returns `true`.
+
+pub use near_riffs_core::*;
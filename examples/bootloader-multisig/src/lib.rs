@@ -0,0 +1,7 @@
+//! # Multisig Bootloader Contract
+//!
+//! A bootloader whose privileged actions, e.g. `redeploy`, are approved by M of N members.
+//! Once the contract owns itself, only confirmed actions pass the owner checks.
+
+pub use near_riffs_core::*;
+pub use near_riffs_multisig::*;
@@ -0,0 +1,7 @@
+//! # Timelocked Bootloader Contract
+//!
+//! A bootloader whose upgrades are delayed. `redeploy` schedules the upgrade,
+//! which the owner can execute with `execute_redeploy` once the delay has passed
+//! or cancel with `cancel_redeploy`.
+
+pub use near_riffs_core::*;
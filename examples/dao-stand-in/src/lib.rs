@@ -0,0 +1,76 @@
+//! # DAO Stand-in Contract
+//!
+//! Stands in for a DAO owning a contract built with the `dao_redeploy` feature.
+//! Instead of voting on proposals, any account can approve a redeploy. An approval
+//! covers all the arguments that change what ends up deployed, including the
+//! migration and the gas budgets, and is consumed by the redeploy it approves.
+
+use near_riffs::near_sdk::{
+    self,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+    json_types::{Base58CryptoHash, Base64VecU8},
+    near_bindgen,
+    serde::{Deserialize, Serialize},
+    AccountId, Gas,
+};
+
+/// A redeploy of `contract_id` the DAO agreed to
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Approval {
+    pub contract_id: AccountId,
+    pub registry: AccountId,
+    pub version: String,
+    pub code_hash: Base58CryptoHash,
+    pub migrate: Option<(String, Base64VecU8)>,
+    /// Approved with the rest, so a caller can't make the redeploy run out of gas
+    pub fetch_gas: Option<Gas>,
+    pub deploy_gas: Option<Gas>,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct Dao {
+    approved: Vec<Approval>,
+}
+
+#[near_bindgen]
+impl Dao {
+    pub fn approve(&mut self, approval: Approval) {
+        self.approved.push(approval);
+    }
+
+    /// Called by the Redeployer before deploying, with the arguments of `redeploy`.
+    /// Only an approval matching all of them, made for the calling contract, is used up.
+    pub fn is_redeploy_approved(
+        &mut self,
+        registry: AccountId,
+        version: String,
+        code_hash: Base58CryptoHash,
+        migrate: Option<(String, Base64VecU8)>,
+        fetch_gas: Option<Gas>,
+        deploy_gas: Option<Gas>,
+    ) -> bool {
+        let request = Approval {
+            contract_id: env::predecessor_account_id(),
+            registry,
+            version,
+            code_hash,
+            migrate,
+            fetch_gas,
+            deploy_gas,
+        };
+        match self
+            .approved
+            .iter()
+            .position(|approval| *approval == request)
+        {
+            Some(index) => {
+                self.approved.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
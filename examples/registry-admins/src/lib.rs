@@ -0,0 +1,6 @@
+//! # Registry with Admins
+//!
+//! A registry where admins added by the owner with `add_admin`, such as CI bots,
+//! can publish patch releases. Minor and major releases stay owner-only.
+
+pub use near_riffs_registry::*;
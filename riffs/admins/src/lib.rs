@@ -24,10 +24,10 @@ impl IntoKey for Admins {
 
 impl Admins {
     pub fn assert_owner_or_admin() {
-        let this = Admins::get_lazy().unwrap();
         let pred = env::predecessor_account_id();
         require!(
-            this.is_admin(&pred) || Owner::get_lazy().unwrap().is_owner(pred),
+            Admins::get_lazy().map_or(false, |this| this.is_admin(&pred))
+                || Owner::predecessor_is_owner(),
             "Not allowed: must be owner or admin"
         );
     }
@@ -35,9 +35,18 @@ impl Admins {
 
 #[near_bindgen(riff)]
 impl Admins {
+    #[payable]
     pub fn add_admin(&mut self, account_id: AccountId) {
-        Self::assert_owner_or_admin();
-        self.admins.push(account_id);
+        Owner::assert_with_one_yocto();
+        if !self.admins.contains(&account_id) {
+            self.admins.push(account_id);
+        }
+    }
+
+    #[payable]
+    pub fn remove_admin(&mut self, account_id: AccountId) {
+        Owner::assert_with_one_yocto();
+        self.admins.retain(|admin| *admin != account_id);
     }
 
     pub fn get_admins(&self) -> Vec<AccountId> {
@@ -0,0 +1,99 @@
+//! # Redeploy allowlist
+//!
+//! Lets the owner restrict `redeploy` to trusted registries and/or approved code hashes
+//! without building a separate wasm, like `parent_only_redeploy` does at compile time.
+use crate::Owner;
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        json_types::Base58CryptoHash,
+        near_bindgen, require, AccountId, CryptoHash,
+    },
+    prelude::*,
+};
+
+pub const ALLOWLIST_KEY: &str = "ALLOWLIST";
+
+/// Registries and code hashes `redeploy` is restricted to.
+/// Each list only restricts redeploys once it is non-empty, and can't be emptied again
+/// since that would silently allow everything.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct Allowlist {
+    registries: Vec<AccountId>,
+    code_hashes: Vec<Base58CryptoHash>,
+}
+
+impl IntoKey for Allowlist {
+    fn into_storage_key() -> Vec<u8> {
+        ALLOWLIST_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Allowlist {
+    pub fn assert_registry_allowed(registry: &AccountId) {
+        if let Some(this) = Self::get_lazy() {
+            require!(
+                this.registries.is_empty() || this.registries.contains(registry),
+                "Registry is not trusted"
+            );
+        }
+    }
+
+    pub fn assert_code_hash_allowed(code_hash: CryptoHash) {
+        if let Some(this) = Self::get_lazy() {
+            require!(
+                this.code_hashes.is_empty() || this.code_hashes.contains(&code_hash.into()),
+                "Code hash is not approved"
+            );
+        }
+    }
+}
+
+#[near_bindgen(riff)]
+impl Allowlist {
+    #[payable]
+    pub fn add_trusted_registry(&mut self, account_id: AccountId) {
+        Owner::assert_with_one_yocto();
+        if !self.registries.contains(&account_id) {
+            self.registries.push(account_id);
+        }
+    }
+
+    #[payable]
+    pub fn remove_trusted_registry(&mut self, account_id: AccountId) {
+        Owner::assert_with_one_yocto();
+        self.registries.retain(|registry| *registry != account_id);
+        require!(
+            !self.registries.is_empty(),
+            "Cannot remove the last trusted registry, which would allow any registry"
+        );
+    }
+
+    #[payable]
+    pub fn add_approved_code_hash(&mut self, code_hash: Base58CryptoHash) {
+        Owner::assert_with_one_yocto();
+        if !self.code_hashes.contains(&code_hash) {
+            self.code_hashes.push(code_hash);
+        }
+    }
+
+    #[payable]
+    pub fn remove_approved_code_hash(&mut self, code_hash: Base58CryptoHash) {
+        Owner::assert_with_one_yocto();
+        self.code_hashes.retain(|approved| *approved != code_hash);
+        require!(
+            !self.code_hashes.is_empty(),
+            "Cannot remove the last approved code hash, which would allow any code"
+        );
+    }
+
+    pub fn trusted_registries(&self) -> &Vec<AccountId> {
+        &self.registries
+    }
+
+    pub fn approved_code_hashes(&self) -> &Vec<Base58CryptoHash> {
+        &self.code_hashes
+    }
+}
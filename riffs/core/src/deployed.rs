@@ -0,0 +1,119 @@
+use crate::{redeploy::OnRedeploy, Owner, Redeployer};
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env,
+        json_types::Base58CryptoHash,
+        near_bindgen, require,
+        serde::Serialize,
+        AccountId,
+    },
+    prelude::*,
+};
+
+pub const DEPLOYED_KEY: &str = "DEPLOYED";
+
+/// Number of deployments remembered, i.e. how many times `rollback` can be called in a row
+const HISTORY_LEN: usize = 5;
+
+/// Code deployed by the Redeployer
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Deployment {
+    pub registry: AccountId,
+    pub version: String,
+    pub code_hash: Base58CryptoHash,
+}
+
+/// Recent deployments, the currently live one last.
+///
+/// Recorded by `on_deployed` once the deploy and its migration succeeded. Code deployed by
+/// a factory is recorded with `seed_deployment`, so the first upgrade can be rolled back.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct Deployed(Vec<Deployment>);
+
+impl IntoKey for Deployed {
+    fn into_storage_key() -> Vec<u8> {
+        DEPLOYED_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Deployed {
+    /// Push a new deployment, or for a rollback drop the deployment being replaced
+    pub fn record(deployment: Deployment, rollback: bool) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        if rollback {
+            this.0.pop();
+        } else {
+            if this.0.len() == HISTORY_LEN {
+                this.0.remove(0);
+            }
+            this.0.push(deployment);
+        }
+        Self::set_lazy(this);
+    }
+}
+
+#[near_bindgen(riff)]
+impl Deployed {
+    /// Redeploy the version deployed before the current one
+    #[payable]
+    pub fn rollback(&self) {
+        Owner::assert_with_one_yocto();
+        let previous = self
+            .0
+            .len()
+            .checked_sub(2)
+            .and_then(|index| self.0.get(index))
+            .cloned()
+            .unwrap_or_else(|| env::panic_str("No previous deployment to roll back to"));
+        Redeployer::start_redeploy(OnRedeploy {
+            registry: previous.registry,
+            version: previous.version,
+            code_hash: Some(previous.code_hash),
+            migrate: None,
+            rollback: true,
+            fetch_gas: None,
+            deploy_gas: None,
+        });
+    }
+
+    /// Record the code the contract was created with, so its first upgrade can be rolled back.
+    /// Called by the account that created this one, e.g. a factory, or by the owner,
+    /// and only before any deployment is recorded.
+    pub fn seed_deployment(
+        &mut self,
+        registry: AccountId,
+        version: String,
+        code_hash: Base58CryptoHash,
+    ) {
+        require!(self.0.is_empty(), "Deployments are already recorded");
+        let predecessor = env::predecessor_account_id();
+        let is_creator = env::current_account_id()
+            .as_str()
+            .split_once('.')
+            .map_or(false, |(_, parent)| parent == predecessor.as_str());
+        require!(
+            is_creator || Owner::predecessor_is_owner(),
+            "Only the owner or the creating account can seed the deployments"
+        );
+        self.0.push(Deployment {
+            registry,
+            version,
+            code_hash,
+        });
+    }
+
+    /// Registry, version and code hash of the live code,
+    /// `null` if it wasn't deployed with `redeploy`
+    pub fn deployed_version(&self) -> Option<&Deployment> {
+        self.0.last()
+    }
+
+    /// Recent deployments, oldest first
+    pub fn deployment_history(&self) -> &Vec<Deployment> {
+        &self.0
+    }
+}
@@ -1,5 +1,17 @@
 pub mod redeploy;
+pub mod allowlist;
+pub mod deployed;
+pub mod migrate;
 pub mod owner;
+pub mod release_keys;
+#[cfg(feature = "timelock")]
+pub mod timelock;
 
-pub use owner::Owner;
-pub use redeploy::Redeployer;
\ No newline at end of file
+pub use allowlist::Allowlist;
+pub use deployed::{Deployed, Deployment};
+pub use migrate::Migrate;
+pub use owner::{Frozen, Owner, PendingOwner};
+pub use redeploy::Redeployer;
+pub use release_keys::ReleaseKeys;
+#[cfg(feature = "timelock")]
+pub use timelock::Timelock;
@@ -0,0 +1,47 @@
+//! # Migrations
+//!
+//! `redeploy` can call a method on the newly deployed code in the same batch as the deploy,
+//! so the deploy and the migration succeed or fail together. By convention this method is
+//! `migrate`, and it migrates each riff whose layout changed:
+//!
+//! ```ignore
+//! impl Migrate for Message {
+//!     type Old = OldMessage;
+//!
+//!     fn migrate(old: OldMessage) -> Self {
+//!         Message { text: old.text, author: None }
+//!     }
+//! }
+//!
+//! #[no_mangle]
+//! pub fn migrate() {
+//!     Message::migrate_lazy();
+//! }
+//! ```
+use near_riffs::{
+    account::assert_private,
+    near_sdk::{borsh::BorshDeserialize, env},
+    prelude::{IntoKey, Lazy},
+};
+
+/// Method called on the new code when `redeploy` is given `migrate_args` but no method
+pub const MIGRATE_METHOD: &str = "migrate";
+
+/// Implemented by riffs whose storage layout changed since the previously deployed version
+pub trait Migrate: IntoKey + Lazy {
+    /// Layout of the riff in the previous version
+    type Old: BorshDeserialize;
+
+    fn migrate(old: Self::Old) -> Self;
+
+    /// Read the riff in its old layout, migrate it and write it back.
+    /// Only the contract itself can migrate, e.g. in the batch created by `on_redeploy`.
+    fn migrate_lazy() {
+        assert_private();
+        if let Some(bytes) = env::storage_read(&Self::into_storage_key()) {
+            let old = Self::Old::try_from_slice(&bytes)
+                .unwrap_or_else(|_| env::panic_str("Cannot deserialize riff to migrate"));
+            Self::set_lazy(Self::migrate(old));
+        }
+    }
+}
@@ -10,6 +10,8 @@ use near_riffs::{
 };
 
 pub const OWNER_KEY: &str = "OWNER";
+pub const PENDING_OWNER_KEY: &str = "PENDING_OWNER";
+pub const FROZEN_KEY: &str = "FROZEN";
 
 #[derive(BorshSerialize, BorshDeserialize, Default)]
 #[near_bindgen(riff)]
@@ -21,9 +23,43 @@ impl IntoKey for Owner {
     }
 }
 
+/// Account proposed by the owner to take over the contract.
+/// Ownership only changes hands once this account calls `accept_owner`.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct PendingOwner(pub Option<AccountId>);
+
+impl IntoKey for PendingOwner {
+    fn into_storage_key() -> Vec<u8> {
+        PENDING_OWNER_KEY.as_bytes().to_vec()
+    }
+}
+
+/// Written when the owner renounces ownership and never removed.
+/// A frozen contract can't be claimed again, so it can never be redeployed.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct Frozen;
+
+impl IntoKey for Frozen {
+    fn into_storage_key() -> Vec<u8> {
+        FROZEN_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Frozen {
+    pub fn is_frozen() -> bool {
+        env::storage_has_key(&Self::into_storage_key())
+    }
+}
+
 impl Owner {
     pub fn assert_owner() {
-        require!(Self::predecessor_is_owner(), "Predecessor is not owner")
+        if !Self::predecessor_is_owner() {
+            if Frozen::is_frozen() {
+                env::panic_str("Contract is frozen")
+            }
+            env::panic_str("Predecessor is not owner")
+        }
     }
 
     pub fn assert_with_one_yocto() {
@@ -32,38 +68,97 @@ impl Owner {
     }
 
     pub fn predecessor_is_owner() -> bool {
-        Owner::get_lazy()
-            .unwrap()
-            .is_owner(env::predecessor_account_id())
+        Owner::get_lazy().map_or(false, |owner| owner.is_owner(env::predecessor_account_id()))
     }
 
     pub fn as_str(&self) -> &str {
         self.0.as_ref().unwrap().as_str()
     }
+
+    fn propose(account_id: AccountId) {
+        Owner::assert_with_one_yocto();
+        PendingOwner::set_lazy(PendingOwner(Some(account_id)));
+    }
 }
 
 #[near_bindgen(riff)]
 impl Owner {
+    /// Claims the contract if it has no owner yet.
+    /// Once owned, this proposes the new owner, who must then call `accept_owner`.
+    #[payable]
     pub fn set_owner(&mut self) {
-        if let Some(owner) = &self.0 {
-            require!(
-                *owner == env::predecessor_account_id(),
-                "only owner can transfer ownership"
-            )
-        }
         let account_id = input::account_id();
-        self.0 = Some(account_id);
+        if self.0.is_some() {
+            Self::propose(account_id);
+        } else {
+            require!(!Frozen::is_frozen(), "Contract is frozen");
+            self.0 = Some(account_id);
+        }
+    }
+
+    /// Propose a new owner, which takes effect once they call `accept_owner`.
+    /// Proposing again replaces the previous proposal.
+    #[payable]
+    pub fn propose_owner() {
+        Self::propose(input::account_id());
     }
 
+    /// Called by the pending owner to complete the transfer of ownership
+    #[payable]
+    pub fn accept_owner(&mut self) {
+        assert_one_yocto();
+        let pending = PendingOwner::get_lazy()
+            .and_then(|pending| pending.0)
+            .unwrap_or_else(|| env::panic_str("No pending owner"));
+        require!(
+            pending == env::predecessor_account_id(),
+            "Only the pending owner can accept ownership"
+        );
+        PendingOwner::set_lazy(PendingOwner(None));
+        self.0 = Some(pending);
+    }
+
+    /// Withdraw the current proposal, if any
+    #[payable]
+    pub fn cancel_owner_proposal() {
+        Owner::assert_with_one_yocto();
+        PendingOwner::set_lazy(PendingOwner(None));
+    }
+
+    /// Permanently give up ownership. The contract is frozen: no one can claim it
+    /// and it can no longer be redeployed.
+    #[payable]
+    pub fn renounce_ownership(&mut self) {
+        Owner::assert_with_one_yocto();
+        PendingOwner::set_lazy(PendingOwner(None));
+        Frozen::set_lazy(Frozen);
+        self.0 = None;
+    }
+
+    /// Returns the owner's account id, or nothing if the contract is unclaimed or frozen
     pub fn get_owner(&self) {
-        env::value_return(self.0.as_ref().unwrap().as_bytes())
+        if let Some(owner) = &self.0 {
+            env::value_return(owner.as_bytes())
+        }
     }
 
-    pub fn get_owner_json(&self) -> &AccountId {
-        self.0.as_ref().unwrap()
+    pub fn get_owner_json(&self) -> Option<&AccountId> {
+        self.0.as_ref()
     }
 
     pub fn is_owner(self, account_id: AccountId) -> bool {
-        self.0.unwrap() == account_id
+        self.0 == Some(account_id)
+    }
+
+    /// Whether ownership has been renounced
+    pub fn is_frozen() -> bool {
+        Frozen::is_frozen()
+    }
+}
+
+#[near_bindgen(riff)]
+impl PendingOwner {
+    pub fn get_pending_owner(&self) -> Option<&AccountId> {
+        self.0.as_ref()
     }
 }
@@ -1,15 +1,40 @@
-use crate::Owner;
+use crate::{migrate::MIGRATE_METHOD, Allowlist, Deployed, Deployment, Owner, ReleaseKeys};
 use near_riffs::{
     account::assert_private,
     input,
-    near_sdk::{self, env, near_bindgen, require, AccountId, Gas},
+    near_sdk::{
+        self, base64,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env,
+        json_types::{Base58CryptoHash, Base64VecU8},
+        near_bindgen, require,
+        serde::Serialize,
+        AccountId, CryptoHash, Gas, GasWeight, PromiseResult,
+    },
     near_units::parse_gas,
     prelude::Lazy,
-    reg,
+    promise, reg,
+    signature::ReleaseSignature,
 };
 
-const FETCH_GAS: u64 = parse_gas!("70 Tgas") as u64;
-const DEPLOY_GAS: u64 = parse_gas!("70 Tgas") as u64;
+const APPROVAL_GAS: u64 = parse_gas!("10 Tgas") as u64;
+const ON_APPROVAL_GAS: u64 = parse_gas!("200 Tgas") as u64;
+const SIGNATURE_GAS: u64 = parse_gas!("10 Tgas") as u64;
+const RESOLVE_GAS: u64 = parse_gas!("10 Tgas") as u64;
+const ON_DEPLOYED_GAS: u64 = parse_gas!("10 Tgas") as u64;
+
+/// Share of the unused gas given to `fetch` when no gas is specified
+const FETCH_WEIGHT: GasWeight = GasWeight(1);
+/// Share of the unused gas given to `on_redeploy`, which deploys and migrates
+const DEPLOY_WEIGHT: GasWeight = GasWeight(2);
+
+/// Called on the owner with the `OnRedeploy` arguments as JSON when using `dao_redeploy`.
+/// Since anyone can call `redeploy`, the owner must only return `true` for an approval
+/// covering the registry, version, code hash, migration and gas budgets, and should use it
+/// up so it can't be replayed. The approved gas budgets have to fit in the fixed gas of
+/// `on_redeploy_approved`, which `redeploy` reserves up front, so a caller can't burn an
+/// approval with too little gas.
+pub const APPROVAL_METHOD: &str = "is_redeploy_approved";
 
 #[derive(Default)]
 #[near_bindgen]
@@ -23,27 +48,138 @@ impl Lazy for Redeployer {
     fn set_lazy(_: Self) {}
 }
 
+/// Arguments passed from `redeploy` to the `on_redeploy` callback
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OnRedeploy {
+    /// Registry the code is fetched from
+    pub registry: AccountId,
+    /// Version passed to the registry's `fetch`
+    pub version: String,
+    /// Expected sha256 hash of the fetched bytes
+    pub code_hash: Option<Base58CryptoHash>,
+    /// Method and arguments called on the new code in the same batch as the deploy
+    pub migrate: Option<(String, Base64VecU8)>,
+    /// Whether this is returning to the previous deployment
+    pub rollback: bool,
+    /// Gas for `fetch`, otherwise a share of the unused prepaid gas
+    pub fetch_gas: Option<Gas>,
+    /// Gas for `on_redeploy`, otherwise a share of the unused prepaid gas
+    pub deploy_gas: Option<Gas>,
+}
+
 impl Redeployer {
     pub fn redeploy() {
-        Owner::assert_with_one_yocto();
-        let (arguments, account_id) = parse_input();
+        let on_redeploy = parse_input();
         if cfg!(feature = "parent_only_redeploy") {
             let this_contract = env::current_account_id();
             let parent = this_contract.as_str().split_once('.').map(|t| t.1).unwrap();
             require!(
-                account_id.as_str() == parent,
+                on_redeploy.registry.as_str() == parent,
                 "Can only deploy to parent account"
             );
         }
-        Self::redeploy_account(account_id, &arguments);
+        if cfg!(feature = "dao_redeploy") {
+            Self::request_approval(&on_redeploy);
+        } else {
+            Owner::assert_with_one_yocto();
+            Self::start_redeploy(on_redeploy);
+        }
+    }
+
+    /// Ask the owner whether the redeploy is approved, continuing in `on_redeploy_approved`.
+    /// Anyone can request it, but the code hash has to be provided and the owner approves
+    /// it together with the rest of the arguments, so a caller can't add a `migrate`
+    /// or change the gas budgets.
+    pub fn request_approval(on_redeploy: &OnRedeploy) {
+        require!(
+            on_redeploy.code_hash.is_some(),
+            "code_hash is required to request approval"
+        );
+        let owner = Owner::get_lazy()
+            .and_then(|owner| owner.0)
+            .unwrap_or_else(|| env::panic_str("Contract has no owner to approve redeploy"));
+        let arguments = near_sdk::serde_json::to_vec(on_redeploy)
+            .unwrap_or_else(|_| env::panic_str("Cannot serialize approval arguments"));
+        let id = env::promise_create(owner, APPROVAL_METHOD, &arguments, 0, Gas(APPROVAL_GAS));
+        env::promise_return(promise::promise_then_for_current_with_input(
+            id,
+            "on_redeploy_approved",
+            0,
+            Gas(ON_APPROVAL_GAS),
+        ))
+    }
+
+    /// Receives the input of `redeploy`
+    pub fn on_redeploy_approved() {
+        assert_private();
+        require!(
+            promise::promise_result_is_true(0),
+            "Redeploy was not approved by owner"
+        );
+        Self::start_redeploy(parse_input());
     }
 
     pub fn on_redeploy() {
         assert_private();
         let promise_value_reg = reg::promise_result(0);
-        env::promise_return(reg::promise_batch_action_deploy_contract_for_current(
-            promise_value_reg,
-        ))
+        let args = OnRedeploy::try_from_slice(&env::input().unwrap_or_default())
+            .unwrap_or_else(|_| env::panic_str("Cannot deserialize on_redeploy arguments"));
+        let code_hash = reg::sha256(promise_value_reg);
+        if let Some(expected) = args.code_hash {
+            require!(
+                code_hash == CryptoHash::from(expected),
+                "Fetched code does not match expected code hash"
+            );
+        }
+        Allowlist::assert_code_hash_allowed(code_hash);
+        ReleaseKeys::assert_signed(&code_hash, fetched_signature);
+        let deployment = Deployment {
+            registry: args.registry,
+            version: resolved_version().unwrap_or(args.version),
+            code_hash: code_hash.into(),
+        };
+        let promise_index =
+            reg::promise_batch_action_deploy_contract_for_current(promise_value_reg);
+        if let Some((method, arguments)) = args.migrate {
+            // Part of the deploy's batch so a failed migration also reverts the deploy
+            env::promise_batch_action_function_call_weight(
+                promise_index,
+                &method,
+                &arguments.0,
+                0,
+                Gas(0),
+                GasWeight(1),
+            );
+        }
+        // Only recorded once the deploy and the migration succeeded. The callback runs on the
+        // new code, so it isn't returned: a binary without `on_deployed` is still deployed,
+        // it just doesn't record the deployment.
+        let callback = env::promise_batch_then(promise_index, &env::current_account_id());
+        let callback_args = (deployment, args.rollback)
+            .try_to_vec()
+            .unwrap_or_else(|_| env::panic_str("Cannot serialize on_deployed arguments"));
+        env::promise_batch_action_function_call(
+            callback,
+            "on_deployed",
+            &callback_args,
+            0,
+            Gas(ON_DEPLOYED_GAS),
+        );
+        env::promise_return(promise_index)
+    }
+
+    /// Called on the new code after the deploy, with the deployment and whether it's a rollback
+    pub fn on_deployed() {
+        assert_private();
+        require!(
+            matches!(env::promise_result(0), PromiseResult::Successful(_)),
+            "Redeploy failed"
+        );
+        let (deployment, rollback) =
+            <(Deployment, bool)>::try_from_slice(&env::input().unwrap_or_default())
+                .unwrap_or_else(|_| env::panic_str("Cannot deserialize on_deployed arguments"));
+        Deployed::record(deployment, rollback);
     }
 }
 
@@ -57,30 +193,178 @@ pub fn on_redeploy() {
     Redeployer::on_redeploy()
 }
 
+#[no_mangle]
+pub fn on_deployed() {
+    Redeployer::on_deployed()
+}
+
+#[cfg(feature = "dao_redeploy")]
+#[no_mangle]
+pub fn on_redeploy_approved() {
+    Redeployer::on_redeploy_approved()
+}
+
 impl Redeployer {
-    pub fn redeploy_account(account_id: AccountId, arguments: &[u8]) {
-        let id = env::promise_create(account_id, "fetch", arguments, 0, Gas(FETCH_GAS));
-        env::promise_return(reg::promise_then_for_current(
-            id,
+    /// Redeploy right away or, with the `timelock` feature, schedule the redeploy
+    pub(crate) fn start_redeploy(on_redeploy: OnRedeploy) {
+        #[cfg(feature = "timelock")]
+        crate::Timelock::schedule(on_redeploy);
+        #[cfg(not(feature = "timelock"))]
+        Self::redeploy_account(on_redeploy);
+    }
+
+    /// Fetch a version from a registry and deploy it to the current account.
+    /// `on_redeploy` also describes how the fetched bytes are checked and migrated.
+    pub fn redeploy_account(on_redeploy: OnRedeploy) {
+        Allowlist::assert_registry_allowed(&on_redeploy.registry);
+        let fetch = env::promise_batch_create(&on_redeploy.registry);
+        let (gas, weight) = gas_and_weight(on_redeploy.fetch_gas, FETCH_WEIGHT);
+        env::promise_batch_action_function_call_weight(
+            fetch,
+            "fetch",
+            on_redeploy.version.as_bytes(),
+            0,
+            gas,
+            weight,
+        );
+        // Resolve a channel or the current version, so the deployment records the version
+        // that was actually fetched
+        let resolve = env::promise_create(
+            on_redeploy.registry.clone(),
+            "resolve_version",
+            on_redeploy.version.as_bytes(),
+            0,
+            Gas(RESOLVE_GAS),
+        );
+        let mut joined = vec![fetch, resolve];
+        // Fetch the signature alongside the code when one is required
+        if ReleaseKeys::is_required() {
+            joined.push(env::promise_create(
+                on_redeploy.registry.clone(),
+                "fetch_signature",
+                on_redeploy.version.as_bytes(),
+                0,
+                Gas(SIGNATURE_GAS),
+            ));
+        }
+        let fetch = env::promise_and(&joined);
+        let callback = env::promise_batch_then(fetch, &env::current_account_id());
+        let (gas, weight) = gas_and_weight(on_redeploy.deploy_gas, DEPLOY_WEIGHT);
+        let callback_args = on_redeploy
+            .try_to_vec()
+            .unwrap_or_else(|_| env::panic_str("Cannot serialize on_redeploy arguments"));
+        env::promise_batch_action_function_call_weight(
+            callback,
             "on_redeploy",
-            &[],
+            &callback_args,
             0,
-            DEPLOY_GAS,
-        ))
+            gas,
+            weight,
+        );
+        env::promise_return(callback)
+    }
+}
+
+/// Version returned by the registry's `resolve_version`, joined with `fetch`.
+/// `None` if the registry doesn't resolve versions, in which case the requested one is kept.
+fn resolved_version() -> Option<String> {
+    if env::promise_results_count() < 2 {
+        return None;
+    }
+    match env::promise_result(1) {
+        PromiseResult::Successful(version) => near_sdk::serde_json::from_slice(&version).ok(),
+        _ => None,
     }
 }
 
-fn parse_input() -> (Vec<u8>, AccountId) {
-    // v0_0_1.tenk.near
+/// Signature returned by the registry's `fetch_signature`, joined with `fetch`
+fn fetched_signature() -> Option<ReleaseSignature> {
+    if env::promise_results_count() < 3 {
+        return None;
+    }
+    match env::promise_result(2) {
+        PromiseResult::Successful(signature) => near_sdk::serde_json::from_slice(&signature)
+            .unwrap_or_else(|_| env::panic_str("Cannot deserialize release signature")),
+        _ => None,
+    }
+}
+
+/// A fixed amount of gas if given, otherwise a share of the unused gas
+fn gas_and_weight(gas: Option<Gas>, weight: GasWeight) -> (Gas, GasWeight) {
+    match gas {
+        Some(gas) => (gas, GasWeight(0)),
+        None => (Gas(0), weight),
+    }
+}
+
+/// Parses `{"registry": "registry.near", "version": "0_0_1", ...}`, where only `registry`
+/// is required, or the short form `v0_0_1.registry.near` as a raw or JSON string,
+/// or `{"account_id": "v0_0_1.registry.near", ...}`
+fn parse_input() -> OnRedeploy {
+    let input = input::input_as_str();
+    let (registry, version) = match input::json_key(&input, "registry") {
+        Some(registry) => (
+            registry,
+            input::json_key(&input, "version").unwrap_or_default(),
+        ),
+        None => parse_short_form(&input),
+    };
+    let registry = registry
+        .parse()
+        .unwrap_or_else(|_| env::panic_str("registry must be a valid account id"));
+    // Strip the `v` of `v0_0_1`, but not of a channel like `vnext`
+    let version = match version.strip_prefix('v') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest.to_string(),
+        _ => version,
+    };
+    let code_hash = input::json_key(&input, "code_hash").map(|code_hash| {
+        code_hash
+            .parse::<Base58CryptoHash>()
+            .unwrap_or_else(|_| env::panic_str("code_hash must be a base58 encoded sha256 hash"))
+    });
+    let migrate_args = input::json_key(&input, "migrate_args").map(|args| {
+        base64::decode(args).unwrap_or_else(|_| env::panic_str("migrate_args must be base64"))
+    });
+    let migrate = input::json_key(&input, "migrate")
+        .or_else(|| migrate_args.is_some().then(|| MIGRATE_METHOD.to_string()))
+        .map(|method| (method, Base64VecU8(migrate_args.unwrap_or_default())));
+    let fetch_gas = parse_gas(&input, "fetch_gas");
+    let deploy_gas = parse_gas(&input, "deploy_gas");
+    OnRedeploy {
+        registry,
+        version,
+        code_hash,
+        migrate,
+        rollback: false,
+        fetch_gas,
+        deploy_gas,
+    }
+}
+
+/// Splits `v0_0_1.tenk.near` into the registry and version
+fn parse_short_form(input: &str) -> (String, String) {
     // Currently checking string adds 10K to contract
-    let input_account_id: String = input::account_id().into();
-    let (version, subaccount) = input_account_id.as_str().split_once('.').unwrap();
-    let arguments = version
-        .strip_prefix('v')
-        .unwrap_or(version)
-        .as_bytes()
-        .to_vec();
-    (arguments, subaccount.parse().unwrap())
+    let short_form = if input.starts_with(['"', '{']) {
+        input::parse_json_or_string(input, "account_id")
+            .unwrap_or_else(|_| env::panic_str("Expected registry or account_id"))
+    } else {
+        input.to_string()
+    };
+    let (version, registry) = short_form.split_once('.').unwrap_or_else(|| {
+        env::panic_str("Expected version and registry, e.g. v0_0_1.registry.near")
+    });
+    // A pre-release tag may contain dots, e.g. `v1_3_0-rc.1`, so the split above is ambiguous
+    let tag = version.split_once('@').map_or(version, |(_, tag)| tag);
+    let is_version = tag.starts_with('v') && tag[1..].starts_with(|c: char| c.is_ascii_digit());
+    require!(
+        !(is_version && tag.contains('-')),
+        "Pre-releases need the JSON form, e.g. {\"registry\": \"registry.near\", \"version\": \"1_3_0-rc.1\"}"
+    );
+    (registry.to_string(), version.to_string())
+}
+
+fn parse_gas(input: &str, key: &str) -> Option<Gas> {
+    input::json_u64(input, key).map(Gas)
 }
 
 #[allow(dead_code, unused_variables)]
@@ -88,8 +372,35 @@ mod private {
     use near_riffs::{near_sdk::AccountId, witgen};
 
     /// Redeploys contract from  provided version and registry.
-    /// e.g. `v0_0_1.contract.testnet`
+    /// e.g. `{"registry": "contract.testnet", "version": "0_0_1"}`,
+    /// or in short `v0_0_1.contract.testnet`. Without a version the registry's
+    /// current version is deployed. The version can also be a release channel
+    /// of the registry, e.g. `beta.contract.testnet`, or a package of the registry,
+    /// e.g. `factory@v0_0_1.contract.testnet`. Pre-releases, e.g. `1_3_0-rc.1`, can only be
+    /// passed in the JSON form.
+    /// Registries refuse to serve yanked versions, so those can't be redeployed.
+    /// Once the owner trusts release keys with `add_trusted_release_key`, the code also has
+    /// to be signed by one of them, which is checked with the registry's `fetch_signature`.
+    ///
+    /// Optionally pass the expected base58 sha256 `code_hash` of the contract,
+    /// and the redeploy fails if the fetched bytes don't match.
+    ///
+    /// `migrate` names a method of the new contract, called with the base64 encoded
+    /// `migrate_args` right after it is deployed. Passing only `migrate_args` calls `migrate`.
+    ///
+    /// `fetch_gas` and `deploy_gas` set the gas of fetching and of deploying, which includes
+    /// the migration, as a number or a string of gas units. Otherwise the unused prepaid gas
+    /// is split between them.
     /// @change
     #[witgen]
-    pub fn redeploy(account_id: AccountId) {}
+    pub fn redeploy(
+        registry: AccountId,
+        version: Option<String>,
+        code_hash: Option<String>,
+        migrate: Option<String>,
+        migrate_args: Option<String>,
+        fetch_gas: Option<String>,
+        deploy_gas: Option<String>,
+    ) {
+    }
 }
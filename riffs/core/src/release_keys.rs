@@ -0,0 +1,73 @@
+//! # Trusted release keys
+//!
+//! Lets the owner require that redeployed code was signed by a release key, e.g. one held
+//! by CI. The signature of the code hash is fetched from the registry along with the code.
+use crate::Owner;
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env, near_bindgen, require, CryptoHash, PublicKey,
+    },
+    prelude::*,
+    signature::ReleaseSignature,
+};
+
+pub const RELEASE_KEYS_KEY: &str = "TRUSTED_RELEASE_KEYS";
+
+/// Keys `redeploy` accepts signatures from. Signatures are only required once non-empty.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct ReleaseKeys(Vec<PublicKey>);
+
+impl IntoKey for ReleaseKeys {
+    fn into_storage_key() -> Vec<u8> {
+        RELEASE_KEYS_KEY.as_bytes().to_vec()
+    }
+}
+
+impl ReleaseKeys {
+    pub fn is_required() -> bool {
+        Self::get_lazy().map_or(false, |this| !this.0.is_empty())
+    }
+
+    /// Panics unless a trusted key signed `code_hash`, if any key is trusted.
+    /// `signature` is only called when a signature is required.
+    pub fn assert_signed<F: FnOnce() -> Option<ReleaseSignature>>(
+        code_hash: &CryptoHash,
+        signature: F,
+    ) {
+        if let Some(this) = Self::get_lazy().filter(|this| !this.0.is_empty()) {
+            let signature = signature().unwrap_or_else(|| env::panic_str("Release is not signed"));
+            require!(
+                this.0.contains(&signature.public_key),
+                "Release is not signed by a trusted key"
+            );
+            require!(
+                signature.verify(code_hash),
+                "Release signature does not match the code hash"
+            );
+        }
+    }
+}
+
+#[near_bindgen(riff)]
+impl ReleaseKeys {
+    #[payable]
+    pub fn add_trusted_release_key(&mut self, public_key: PublicKey) {
+        Owner::assert_with_one_yocto();
+        if !self.0.contains(&public_key) {
+            self.0.push(public_key);
+        }
+    }
+
+    #[payable]
+    pub fn remove_trusted_release_key(&mut self, public_key: PublicKey) {
+        Owner::assert_with_one_yocto();
+        self.0.retain(|key| *key != public_key);
+    }
+
+    pub fn trusted_release_keys(&self) -> &Vec<PublicKey> {
+        &self.0
+    }
+}
@@ -0,0 +1,120 @@
+//! # Timelock
+//!
+//! With the `timelock` feature `redeploy` and `rollback` only schedule the upgrade.
+//! After the delay has passed the owner calls `execute_redeploy` to perform it,
+//! and until then the owner can `cancel_redeploy`.
+//!
+//! A scheduled upgrade must include a `code_hash`, since the version can be a channel or
+//! the registry's current version, which the registry owner could change during the delay.
+use crate::{redeploy::OnRedeploy, Owner, Redeployer};
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env,
+        json_types::U64,
+        near_bindgen, require,
+        serde::Serialize,
+    },
+    prelude::*,
+};
+
+pub const TIMELOCK_KEY: &str = "TIMELOCK";
+
+/// One day in nanoseconds
+const DEFAULT_DELAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// One year in nanoseconds, so a typo in the delay can't lock upgrades forever
+const MAX_DELAY: u64 = 365 * DEFAULT_DELAY;
+
+/// Upgrade waiting for its delay to pass
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingRedeploy {
+    #[serde(flatten)]
+    pub upgrade: OnRedeploy,
+    /// Block timestamp in nanoseconds after which the upgrade can be executed
+    pub earliest_timestamp: U64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Timelock {
+    /// Delay in nanoseconds between scheduling and executing an upgrade
+    delay: u64,
+    pending: Option<PendingRedeploy>,
+}
+
+impl Default for Timelock {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_DELAY,
+            pending: None,
+        }
+    }
+}
+
+impl IntoKey for Timelock {
+    fn into_storage_key() -> Vec<u8> {
+        TIMELOCK_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Timelock {
+    /// Schedule an upgrade, replacing any upgrade already pending
+    pub fn schedule(upgrade: OnRedeploy) {
+        require!(
+            upgrade.code_hash.is_some(),
+            "code_hash is required to schedule a redeploy"
+        );
+        let mut this = Self::get_lazy().unwrap_or_default();
+        let earliest_timestamp = env::block_timestamp().saturating_add(this.delay);
+        this.pending = Some(PendingRedeploy {
+            upgrade,
+            earliest_timestamp: earliest_timestamp.into(),
+        });
+        Self::set_lazy(this);
+    }
+}
+
+#[near_bindgen(riff)]
+impl Timelock {
+    /// Perform the pending upgrade once its delay has passed
+    #[payable]
+    pub fn execute_redeploy(&mut self) {
+        Owner::assert_with_one_yocto();
+        let pending = self
+            .pending
+            .take()
+            .unwrap_or_else(|| env::panic_str("No pending redeploy"));
+        require!(
+            env::block_timestamp() >= pending.earliest_timestamp.0,
+            "Redeploy is still timelocked"
+        );
+        Redeployer::redeploy_account(pending.upgrade);
+    }
+
+    #[payable]
+    pub fn cancel_redeploy(&mut self) {
+        Owner::assert_with_one_yocto();
+        require!(self.pending.take().is_some(), "No pending redeploy");
+    }
+
+    pub fn pending_redeploy(&self) -> Option<&PendingRedeploy> {
+        self.pending.as_ref()
+    }
+
+    /// Delay in nanoseconds
+    pub fn redeploy_delay(&self) -> U64 {
+        self.delay.into()
+    }
+
+    /// Set the delay in nanoseconds. It can only be increased, otherwise the owner
+    /// could skip the timelock by setting it to zero, and is at most a year.
+    #[payable]
+    pub fn set_redeploy_delay(&mut self, delay: U64) {
+        Owner::assert_with_one_yocto();
+        require!(delay.0 >= self.delay, "Delay can only be increased");
+        require!(delay.0 <= MAX_DELAY, "Delay can be at most a year");
+        self.delay = delay.0;
+    }
+}
@@ -1,7 +1,10 @@
 use near_riffs::{
     near_sdk::{
-        self, env, json_types::U128, near_bindgen, require, AccountId, Gas, GasWeight, Promise,
-        PromiseResult,
+        self, env,
+        json_types::{Base58CryptoHash, U128},
+        near_bindgen, require,
+        serde_json::json,
+        AccountId, Gas, GasWeight, Promise, PromiseResult,
     },
     near_units::{parse_gas, parse_near as near},
     prelude::*,
@@ -12,6 +15,7 @@ pub use near_riffs_core::*;
 use near_riffs_registry::Registry;
 
 const INIT_GAS: Gas = Gas(parse_gas!("20 Tgas") as u64);
+const SEED_GAS: Gas = Gas(parse_gas!("10 Tgas") as u64);
 const MIN_DEPLOY_DEPOSIT: u128 = near!("6 N");
 
 #[near_bindgen]
@@ -21,12 +25,15 @@ pub struct Factory {}
 impl Factory {
     /// Create new account and deploy a contract, and set's the owne to the predecessor_account_id,
     /// e.i. the account that called this contract
+    ///
+    /// Deploys the current version, or the version of the registry `channel` if given, e.g. `beta`
     /// 
     /// Requires at least 6N = 6000000000000000000000000
     /// @change
     #[payable]
     pub fn create_subaccount_and_deploy(
         new_account_id: AccountId,
+        channel: Option<String>,
     ) {
         let amount = env::attached_deposit();
         require!(
@@ -55,13 +62,17 @@ impl Factory {
         env::promise_batch_action_transfer(promise_index, amount);
 
         // Load the contract's bytes into a register
-        let bytes_reg = Registry::get_lazy().as_ref().map_or_else(
-            || env::panic_str("Failed to fetch registry"),
-            Registry::fetch_to_reg,
-        );
+        let registry = Registry::get_lazy()
+            .unwrap_or_else(|| env::panic_str("Failed to fetch registry"));
+        let version = registry.resolve_key(channel.as_deref().unwrap_or_default());
+        let bytes_reg = match channel {
+            Some(channel) => registry.fetch_version_to_reg(&channel),
+            None => registry.fetch_to_reg(),
+        };
 
         // Use reg module to pass the register instead of byte array
         reg::promise_batch_action_deploy_contract(promise_index, bytes_reg);
+        let code_hash = Base58CryptoHash::from(reg::sha256(bytes_reg));
 
         // Initialize contract with at least the bootloader to be owned by owner_id
         env::promise_batch_action_function_call_weight(
@@ -75,8 +86,14 @@ impl Factory {
 
         // Then attached callback to the current contract
         let final_promise_index = env::promise_batch_then(promise_index, &current_account_id);
-        let args =
-            format!("{{\"predecessor_account_id\":\"{owner_id}\", \"amount\":\"{amount}\"}}");
+        let args = json!({
+            "predecessor_account_id": owner_id,
+            "amount": U128(amount),
+            "new_account_id": new_account_id,
+            "version": version,
+            "code_hash": code_hash,
+        })
+        .to_string();
         env::promise_batch_action_function_call_weight(
             final_promise_index,
             "on_account_created",
@@ -89,10 +106,32 @@ impl Factory {
     }
 
     /// Callback after executing `create_account`.
+    /// On success the deployed version is recorded with `seed_deployment`, so the account's
+    /// first upgrade can be rolled back. That's a separate call whose failure is ignored,
+    /// since binaries built without it, e.g. terminal ones, can't record deployments.
     #[private]
-    pub fn on_account_created(predecessor_account_id: AccountId, amount: U128) -> bool {
+    pub fn on_account_created(
+        predecessor_account_id: AccountId,
+        amount: U128,
+        new_account_id: AccountId,
+        version: String,
+        code_hash: Base58CryptoHash,
+    ) -> bool {
         let creation_succeeded = is_promise_success();
-        if !creation_succeeded {
+        if creation_succeeded {
+            let deployment = json!({
+                "registry": env::current_account_id(),
+                "version": version,
+                "code_hash": code_hash,
+            });
+            env::promise_create(
+                new_account_id,
+                "seed_deployment",
+                deployment.to_string().as_bytes(),
+                0,
+                SEED_GAS,
+            );
+        } else {
             // In case of failure, send funds back.
             Promise::new(predecessor_account_id).transfer(amount.into());
         }
@@ -0,0 +1,230 @@
+//! # Multisig Riff
+//!
+//! Stores a set of members and a threshold. Members propose privileged actions and once
+//! `threshold` members have confirmed an action, the contract calls the method on itself.
+//!
+//! For the owner checks of `redeploy`, `set_owner` and the registry methods to pass, the
+//! contract must own itself: the owner proposes the contract's account with `propose_owner`
+//! and the members confirm a `FunctionCall` action calling `accept_owner` with one yocto.
+use near_riffs::{prelude::*, storage};
+
+use near_riffs::near_sdk::{
+    self, assert_one_yocto,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::UnorderedMap,
+    env,
+    json_types::{Base64VecU8, U128, U64},
+    near_bindgen, require,
+    serde::{Deserialize, Serialize},
+    AccountId, Gas, GasWeight,
+};
+
+pub use near_riffs_core::Owner;
+
+const MULTISIG_KEY: &str = "MULTISIG";
+
+/// Privileged action the contract calls on itself once confirmed
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Action {
+    /// Input to `redeploy`, e.g. `v0_0_1.registry.near`
+    Redeploy { target: String },
+    /// Propose a new owner with `set_owner`
+    SetOwner { account_id: AccountId },
+    /// Publish to the contract's registry with `patch`, `minor` or `major`
+    Publish {
+        method: String,
+        code: Base64VecU8,
+        deposit: U128,
+    },
+    FunctionCall {
+        method_name: String,
+        args: Base64VecU8,
+        deposit: U128,
+    },
+}
+
+impl Action {
+    /// Method, arguments and deposit of the call
+    fn into_call(self) -> (String, Vec<u8>, u128) {
+        match self {
+            Action::Redeploy { target } => ("redeploy".to_string(), target.into_bytes(), 1),
+            Action::SetOwner { account_id } => {
+                ("set_owner".to_string(), account_id.as_bytes().to_vec(), 1)
+            }
+            Action::Publish {
+                method,
+                code,
+                deposit,
+            } => (method, code.0, deposit.0),
+            Action::FunctionCall {
+                method_name,
+                args,
+                deposit,
+            } => (method_name, args.0, deposit.0),
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub action: Action,
+    pub confirmations: Vec<AccountId>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Multisig {
+    members: Vec<AccountId>,
+    threshold: u32,
+    next_id: u64,
+    proposals: UnorderedMap<u64, Proposal>,
+}
+
+impl Default for Multisig {
+    fn default() -> Self {
+        Self {
+            members: vec![],
+            threshold: 0,
+            next_id: 0,
+            proposals: UnorderedMap::new(b"multisig".to_vec()),
+        }
+    }
+}
+
+impl IntoKey for Multisig {
+    fn into_storage_key() -> Vec<u8> {
+        MULTISIG_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Multisig {
+    /// Use instead of `Owner::assert_owner` for methods only a confirmed action can call
+    pub fn assert_approved() {
+        require!(
+            env::predecessor_account_id() == env::current_account_id(),
+            "Not allowed: must be approved by the multisig"
+        );
+    }
+
+    pub fn assert_approved_with_one_yocto() {
+        Multisig::assert_approved();
+        assert_one_yocto()
+    }
+
+    pub fn assert_member() {
+        let this = Multisig::get_lazy().unwrap_or_default();
+        require!(
+            this.is_member(&env::predecessor_account_id()),
+            "Not allowed: must be a multisig member"
+        );
+    }
+
+    fn is_member(&self, account_id: &AccountId) -> bool {
+        self.members.contains(account_id)
+    }
+
+    fn confirm(&mut self, id: u64) {
+        let member = env::predecessor_account_id();
+        require!(
+            self.is_member(&member),
+            "Not allowed: must be a multisig member"
+        );
+        let mut proposal = self
+            .proposals
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("No such proposal"));
+        require!(
+            !proposal.confirmations.contains(&member),
+            "Already confirmed"
+        );
+        proposal.confirmations.push(member);
+        if proposal.confirmations.len() < self.threshold as usize {
+            self.proposals.insert(&id, &proposal);
+            return;
+        }
+        self.proposals.remove(&id);
+        let (method, args, deposit) = proposal.action.into_call();
+        let promise_index = env::promise_batch_create(&env::current_account_id());
+        env::promise_batch_action_function_call_weight(
+            promise_index,
+            &method,
+            &args,
+            deposit,
+            Gas(0),
+            GasWeight(1),
+        );
+        env::promise_return(promise_index)
+    }
+}
+
+#[near_bindgen(riff)]
+impl Multisig {
+    /// Set the members and how many of them must confirm an action.
+    /// Initially called by the owner, afterwards only through a confirmed action.
+    /// Pending proposals are dropped.
+    pub fn set_multisig(&mut self, members: Vec<AccountId>, threshold: u32) {
+        if self.members.is_empty() {
+            Owner::assert_owner();
+        } else {
+            Self::assert_approved();
+        }
+        require!(
+            threshold > 0 && threshold as usize <= members.len(),
+            "Threshold must be between 1 and the number of members"
+        );
+        self.members = members;
+        self.threshold = threshold;
+        self.proposals.clear();
+    }
+
+    /// Propose an action, which counts as the proposer's confirmation.
+    /// The proposer pays for storing the proposal, e.g. the code of a `Publish` action.
+    #[payable]
+    pub fn propose_action(&mut self, action: Action) -> U64 {
+        if let Action::Publish { method, .. } = &action {
+            require!(
+                ["patch", "minor", "major"].contains(&method.as_str()),
+                "Publish method must be patch, minor or major"
+            );
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        storage::refund_cost(|| {
+            self.proposals.insert(
+                &id,
+                &Proposal {
+                    action,
+                    confirmations: vec![],
+                },
+            );
+            self.confirm(id);
+        });
+        id.into()
+    }
+
+    /// Confirm an action, executing it if the threshold is reached
+    pub fn confirm_action(&mut self, id: U64) {
+        self.confirm(id.0);
+    }
+
+    pub fn get_action_proposal(&self, id: U64) -> Option<Proposal> {
+        self.proposals.get(&id.0)
+    }
+
+    pub fn get_action_proposals(&self) -> Vec<(U64, Proposal)> {
+        self.proposals
+            .iter()
+            .map(|(id, proposal)| (id.into(), proposal))
+            .collect()
+    }
+
+    pub fn get_multisig_members(&self) -> Vec<AccountId> {
+        self.members.clone()
+    }
+
+    pub fn get_multisig_threshold(&self) -> u32 {
+        self.threshold
+    }
+}
@@ -0,0 +1,148 @@
+//! # Content-addressed binaries
+//!
+//! Binaries are stored once under their sha256 code hash and versions point at them,
+//! so publishing bytes that are already stored only costs the version's own storage.
+//! Versions published before this riff existed keep their bytes under the version's key.
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        collections::LookupMap,
+        env,
+        json_types::Base58CryptoHash,
+        near_bindgen, require, CryptoHash,
+    },
+    prelude::*,
+    reg,
+    version::Version,
+};
+
+pub const BINARIES_KEY: &str = "BINARIES";
+
+/// Storage key of the binary with the code hash
+fn code_key(code_hash: &CryptoHash) -> Vec<u8> {
+    [b"c".as_slice(), &code_hash[..]].concat()
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Binaries {
+    /// Code hash of each version, keyed by the version's storage key
+    code_hashes: LookupMap<Vec<u8>, CryptoHash>,
+    /// Number of versions pointing at each binary
+    refcounts: LookupMap<CryptoHash, u32>,
+}
+
+impl Default for Binaries {
+    fn default() -> Self {
+        Self {
+            code_hashes: LookupMap::new(b"h".to_vec()),
+            refcounts: LookupMap::new(b"r".to_vec()),
+        }
+    }
+}
+
+impl IntoKey for Binaries {
+    fn into_storage_key() -> Vec<u8> {
+        BINARIES_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Binaries {
+    /// Store `bytes` as the binary of the version with storage key `key`,
+    /// e.g. `1_2_3` or `factory@1_2_3`
+    pub fn store(key: &[u8], bytes: &[u8]) {
+        let code_hash = env::sha256_array(bytes);
+        Self::link(key, code_hash, || {
+            env::storage_write(&code_key(&code_hash), bytes);
+        });
+    }
+
+    /// Point the version with storage key `key` at the binary of `existing`
+    pub fn alias(key: &[u8], existing: &[u8]) {
+        match Self::code_hash_of(existing) {
+            Some(code_hash) => Self::link(key, code_hash, || {}),
+            None => {
+                let bytes =
+                    env::storage_read(existing).unwrap_or_else(|| env::panic_str("MISSING BINARY"));
+                Self::store(key, &bytes)
+            }
+        }
+    }
+
+    /// Point the version at the binary with `code_hash`, calling `write` to store the binary
+    /// if no other version uses it
+    fn link<F: FnOnce()>(key: &[u8], code_hash: CryptoHash, write: F) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        let refcount = this.refcounts.get(&code_hash).unwrap_or_default();
+        if refcount == 0 {
+            write();
+        }
+        this.refcounts.insert(&code_hash, &(refcount + 1));
+        this.code_hashes.insert(&key.to_vec(), &code_hash);
+        Self::set_lazy(this);
+    }
+
+    /// Remove the version's binary, deleting the bytes once no other version uses them
+    pub fn unlink(key: &[u8]) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        match this.code_hashes.remove(&key.to_vec()) {
+            Some(code_hash) => {
+                let refcount = this.refcounts.get(&code_hash).unwrap_or_default();
+                if refcount <= 1 {
+                    this.refcounts.remove(&code_hash);
+                    env::storage_remove(&code_key(&code_hash));
+                } else {
+                    this.refcounts.insert(&code_hash, &(refcount - 1));
+                }
+            }
+            None => {
+                env::storage_remove(key);
+            }
+        }
+        Self::set_lazy(this);
+    }
+
+    pub fn code_hash_of(key: &[u8]) -> Option<CryptoHash> {
+        Self::get_lazy()?.code_hashes.get(&key.to_vec())
+    }
+
+    /// Code hash of the version's binary, including one stored under the version's key
+    pub fn code_hash(key: &[u8]) -> Option<CryptoHash> {
+        Self::code_hash_of(key)
+            .or_else(|| env::storage_read(key).map(|bytes| env::sha256_array(&bytes)))
+    }
+
+    /// Whether the version has a binary, including one stored under the version's key
+    /// before binaries were content-addressed
+    pub fn has_version(key: &[u8]) -> bool {
+        Self::code_hash_of(key).is_some() || env::storage_has_key(key)
+    }
+
+    /// Read the binary of a version into a register
+    pub fn read_to_reg(key: &[u8]) -> Option<u64> {
+        match Self::code_hash_of(key) {
+            Some(code_hash) => reg::storage_read(&code_key(&code_hash)),
+            None => reg::storage_read(key),
+        }
+    }
+}
+
+#[near_bindgen(riff)]
+impl Binaries {
+    /// Fetch a binary by its sha256 code hash
+    pub fn fetch_by_hash(&self, code_hash: Base58CryptoHash) {
+        let code_hash = CryptoHash::from(code_hash);
+        require!(
+            self.refcounts.contains_key(&code_hash),
+            "No binary with this code hash"
+        );
+        let value_reg = reg::storage_read(&code_key(&code_hash)).expect("MISSING BINARY");
+        reg::value_return(value_reg);
+    }
+
+    /// Code hash of a version's binary
+    pub fn version_code_hash(&self, version: Version) -> Option<Base58CryptoHash> {
+        self.code_hashes.get(&version.to_key()).map(Into::into)
+    }
+}
@@ -1,19 +1,78 @@
 use near_riffs::prelude::*;
 use near_riffs::{
-    input::DataUrl,
+    input::{self, DataUrl},
     near_sdk::{
         self,
         borsh::{self, BorshDeserialize, BorshSerialize},
         collections::Vector,
-        env, near_bindgen,
+        env,
+        json_types::U64,
+        near_bindgen, require,
+        serde::Deserialize,
     },
-    reg, storage,
+    reg,
+    signature::ReleaseSignature,
+    storage,
     version::Version,
+    wasm::{self, WasmError},
 };
 
 /// Bootloader riff
 use near_riffs_core::Owner;
 
+#[cfg(feature = "near-riffs-admins")]
+pub use near_riffs_admins::Admins;
+
+pub mod binaries;
+pub mod metadata;
+pub mod mirror;
+pub mod packages;
+pub mod signatures;
+pub mod status;
+pub mod upload;
+
+pub use binaries::Binaries;
+pub use metadata::{Metadata, Release, ReleaseMetadata};
+pub use mirror::Mirror;
+pub use packages::{Package, Packages};
+pub use signatures::Signatures;
+pub use status::{ReleaseStatus, Statuses};
+pub use upload::{ReleaseKind, Upload};
+
+/// Number of releases returned by `versions` and `release_history` when no limit is given
+const DEFAULT_LIMIT: u64 = 50;
+
+/// Arguments of `versions` and `release_history`, all optional so both can still be called
+/// without input. Only `versions` takes `major`.
+#[derive(Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+struct PageArgs {
+    from_index: Option<U64>,
+    limit: Option<U64>,
+    major: Option<u16>,
+}
+
+impl PageArgs {
+    /// Arguments from the JSON input, or the defaults without input
+    fn from_input() -> Self {
+        let input = env::input().unwrap_or_default();
+        if input.is_empty() {
+            Self::default()
+        } else {
+            near_sdk::serde_json::from_slice(&input)
+                .unwrap_or_else(|_| env::panic_str("Failed to deserialize input from JSON."))
+        }
+    }
+
+    fn skip(&self) -> usize {
+        self.from_index.map_or(0, |index| index.0) as usize
+    }
+
+    fn take(&self) -> usize {
+        self.limit.map_or(DEFAULT_LIMIT, |limit| limit.0) as usize
+    }
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Registry {
@@ -34,44 +93,239 @@ impl IntoKey for Registry {
     }
 }
 
+/// Published pre-releases by storage key, e.g. `1_3_0-rc.1`, so build metadata is ignored.
+/// Kept out of `Registry::versions` so a pre-release never becomes the current version.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct PreReleases(Vec<String>);
+
+impl IntoKey for PreReleases {
+    fn into_storage_key() -> Vec<u8> {
+        "PRE_RELEASES".as_bytes().to_vec()
+    }
+}
+
+impl PreReleases {
+    fn push(version: &Version) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        this.0.push(Self::key(version));
+        Self::set_lazy(this);
+    }
+
+    fn contains(version: &Version) -> bool {
+        let key = Self::key(version);
+        Self::get_lazy().map_or(false, |this| this.0.contains(&key))
+    }
+
+    fn key(version: &Version) -> String {
+        String::from_utf8_lossy(&version.to_key()).into_owned()
+    }
+}
+
+#[near_bindgen(riff)]
+impl PreReleases {
+    pub fn pre_releases(&self) -> &Vec<String> {
+        &self.0
+    }
+}
+
+/// Named release channels, e.g. `stable` or `beta`, each pointing at a published version
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct Channels(Vec<(String, String)>);
+
+impl IntoKey for Channels {
+    fn into_storage_key() -> Vec<u8> {
+        "CHANNELS".as_bytes().to_vec()
+    }
+}
+
+impl Channels {
+    fn version(channel: &str) -> Option<Version> {
+        Self::get_lazy()?
+            .0
+            .into_iter()
+            .find(|(name, _)| name == channel)
+            .map(|(_, version)| parse_version(&version))
+    }
+
+    /// Whether any channel points at `version`
+    fn points_at(version: &Version) -> bool {
+        let key = version.to_key();
+        Self::get_lazy().map_or(false, |this| {
+            this.0
+                .iter()
+                .any(|(_, channel_version)| parse_version(channel_version).to_key() == key)
+        })
+    }
+}
+
+#[near_bindgen(riff)]
+impl Channels {
+    /// Point a channel at a published version, creating the channel if needed.
+    /// Channel names can't contain `.` or be a version.
+    #[payable]
+    pub fn set_channel(&mut self, channel: String, version: Version) {
+        Owner::assert_with_one_yocto();
+        require!(
+            !channel.is_empty() && !channel.contains('.') && channel.parse::<Version>().is_err(),
+            "Channel must be a name like stable or beta"
+        );
+        require!(
+            Binaries::has_version(&version.to_key()),
+            "Version is not published"
+        );
+        Statuses::assert_not_yanked(&version.to_key());
+        let version = version.to_string();
+        match self.0.iter_mut().find(|(name, _)| *name == channel) {
+            Some((_, current)) => *current = version,
+            None => self.0.push((channel, version)),
+        }
+    }
+
+    #[payable]
+    pub fn remove_channel(&mut self, channel: String) {
+        Owner::assert_with_one_yocto();
+        self.0.retain(|(name, _)| *name != channel);
+    }
+
+    /// Version the channel points at
+    pub fn channel_version(&self, channel: String) -> Option<&String> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == channel)
+            .map(|(_, version)| version)
+    }
+
+    /// Channel names and their versions
+    pub fn channels(&self) -> &Vec<(String, String)> {
+        &self.0
+    }
+}
+
 #[near_bindgen(riff)]
 impl Registry {
-    /// Non-breaking fix
+    /// Non-breaking fix, taking the raw wasm as input.
+    /// The raw input leaves no room for `terminal`, so the wasm must export the methods
+    /// needed to redeploy it. Publish a terminal binary with `patch_contract` instead.
     #[payable]
     pub fn patch(&mut self) {
-        Owner::assert_owner();
+        assert_can_publish_patch();
         self.input_to_storage(self.current().publish_patch())
     }
 
-    /// Non-breaking feature
+    /// Non-breaking feature, taking the raw wasm as input. Like `patch`, the wasm must be
+    /// redeployable; publish a terminal binary with `finalize_upload` instead.
     #[payable]
     pub fn minor(&mut self) {
         Owner::assert_owner();
         self.input_to_storage(self.current().publish_minor())
     }
 
-    /// Breaking change
+    /// Breaking change, taking the raw wasm as input. Like `patch`, the wasm must be
+    /// redeployable; publish a terminal binary with `finalize_upload` instead.
     #[payable]
     pub fn major(&mut self) {
         Owner::assert_owner();
         self.input_to_storage(self.current().publish_major())
     }
 
-    /// Fetch a version of the contract
-    /// If no argument provided use current version
+    /// Fetch a version of the contract, e.g. `0_0_1` or `1.3.0-rc.1`, the version
+    /// a channel such as `beta` points at, or a package's version, e.g. `factory@0_0_1`.
+    /// If no argument provided use current version, which is never a pre-release or yanked.
+    /// A yanked version is only returned when forced: `{"version": "0_0_1", "force": true}`.
+    /// Pruned versions can't be fetched.
     pub fn fetch(&self) {
         let value_reg = if reg::input_is_empty() {
-            let key: Vec<u8> = (&self.current()).into();
-            reg::storage_read(&key)
+            self.fetch_to_reg()
         } else {
-            reg::storage_read_from_input()
-        }
-        .expect("MISSING BINARY");
+            let input = input::input_as_str();
+            match input::json_key(&input, "version") {
+                Some(version) if input::json_bool(&input, "force") == Some(true) => {
+                    self.version_to_reg(&version, true)
+                }
+                Some(version) => self.fetch_version_to_reg(&version),
+                None => self.fetch_version_to_reg(&input),
+            }
+        };
 
         reg::value_return(value_reg);
     }
 
-    /// Current version of the contract
+    /// Version `fetch` returns for the same input, e.g. `1_2_3` for a channel pointing at
+    /// `v1_2_3`, so redeployed contracts can record which version they got
+    pub fn resolve_version(&self) -> String {
+        let input = input::input_as_str();
+        self.resolve_key(&input::json_key(&input, "version").unwrap_or(input))
+    }
+
+    /// Signature of the binary `fetch` returns for the same input, `null` if it wasn't signed
+    pub fn fetch_signature(&self) -> Option<ReleaseSignature> {
+        let input = input::input_as_str();
+        let key = self.binary_key(&input::json_key(&input, "version").unwrap_or(input));
+        Signatures::get_lazy()?.get(&key)
+    }
+
+    /// Publish a pre-release, e.g. `1.3.0-rc.1`, which can only be fetched by its exact version
+    #[payable]
+    pub fn pre_release(
+        &mut self,
+        version: Version,
+        contract_bytes: DataUrl,
+        metadata: Option<ReleaseMetadata>,
+        terminal: Option<bool>,
+        signature: Option<ReleaseSignature>,
+    ) {
+        Owner::assert_owner();
+        require!(
+            version.is_pre_release(),
+            "Version must have a pre-release tag, e.g. 1.3.0-rc.1"
+        );
+        require!(
+            version > self.current(),
+            "Pre-release must be newer than the current version"
+        );
+        require!(
+            !Binaries::has_version(&version.to_key()),
+            "Version already published"
+        );
+        let contract_bytes = contract_bytes.to_vec();
+        assert_redeployable(&contract_bytes, terminal);
+        storage::refund_cost(|| {
+            Binaries::store(&version.to_key(), &contract_bytes);
+            PreReleases::push(&version);
+            if let Some(metadata) = &metadata {
+                Metadata::record(&version.to_key(), metadata);
+            }
+            if let Some(signature) = &signature {
+                Signatures::record(&version.to_key(), signature);
+            }
+        })
+    }
+
+    /// Publish the binary of a pre-release as its release, e.g. `1.3.0-rc.1` as `1.3.0`,
+    /// along with its metadata and signature. The pre-release can still be fetched.
+    #[payable]
+    pub fn promote(&mut self, version: Version) {
+        Owner::assert_owner();
+        require!(PreReleases::contains(&version), "No such pre-release");
+        let release = version.to_release();
+        require!(
+            release > self.current(),
+            "Release must be newer than the current version"
+        );
+        let metadata = Metadata::get_lazy().and_then(|metadata| metadata.get(&version.to_key()));
+        storage::refund_cost(|| {
+            Binaries::alias(&release.to_key(), &version.to_key());
+            self.versions.push(&release);
+            if let Some(metadata) = &metadata {
+                Metadata::record(&release.to_key(), metadata);
+            }
+            Signatures::copy(&version.to_key(), &release.to_key());
+        })
+    }
+
+    /// Last published version of the contract
     fn current(&self) -> Version {
         let len = self.versions.len();
         if len == 0 {
@@ -83,39 +337,255 @@ impl Registry {
         }
     }
 
+    /// Publish the raw wasm input, which is read into memory once to check its exports
     fn input_to_storage(&mut self, new_version: Version) {
+        let bytes = env::input().unwrap_or_default();
+        assert_redeployable(&bytes, None);
         storage::refund_cost(|| {
-            new_version.input_to_storage();
+            Binaries::store(&new_version.to_key(), &bytes);
             self.versions.push(&new_version);
         })
     }
 
-    /// Current version of the contract
+    /// Latest version that wasn't yanked or pruned
+    fn latest(&self) -> Option<Version> {
+        self.latest_matching(None)
+    }
+
+    /// Current version of the contract, skipping yanked and pruned versions
     pub fn current_version(&self) -> String {
-        self.current().to_string()
+        self.latest().unwrap_or_default().to_string()
+    }
+
+    /// Latest version of a major version, skipping yanked and pruned versions
+    pub fn latest_for_major(&self, major: u16) -> Option<String> {
+        self.latest_matching(Some(major))
+            .map(|version| version.to_string())
     }
 
+    /// Published versions, newest first, starting at `from_index`.
+    /// Pass `major` to only list the versions of a major version, e.g. `1` for `v1_*`.
+    /// Without input the newest versions of all majors are listed.
     pub fn versions(&self) -> Vec<String> {
+        let args = PageArgs::from_input();
+        self.newest_first(args.major)
+            .skip(args.skip())
+            .take(args.take())
+            .map(|version| version.to_string())
+            .collect()
+    }
+
+    /// Releases with their metadata, oldest first, starting at `from_index`.
+    /// Without input the oldest releases are listed.
+    pub fn release_history(&self) -> Vec<Release> {
+        let args = PageArgs::from_input();
+        let metadata = Metadata::get_lazy().unwrap_or_default();
+        let statuses = Statuses::get_lazy().unwrap_or_default();
         self.versions
-            .to_vec()
             .iter()
-            .map(ToString::to_string)
+            .skip(args.skip())
+            .take(args.take())
+            .map(|version| Release {
+                metadata: metadata.get(&version.to_key()),
+                status: statuses.get(&version.to_key()),
+                version: version.to_string(),
+            })
             .collect()
     }
 
-    pub fn patch_contract(&mut self, contract_bytes: DataUrl) {
-        Owner::assert_owner();
+    #[payable]
+    pub fn patch_contract(
+        &mut self,
+        contract_bytes: DataUrl,
+        metadata: Option<ReleaseMetadata>,
+        terminal: Option<bool>,
+        signature: Option<ReleaseSignature>,
+    ) {
+        assert_can_publish_patch();
+        let contract_bytes = contract_bytes.to_vec();
+        assert_redeployable(&contract_bytes, terminal);
+        storage::refund_cost(|| {
+            let new_version = self.current().publish_patch();
+            self.versions.push(&new_version);
+            Binaries::store(&new_version.to_key(), &contract_bytes);
+            if let Some(metadata) = &metadata {
+                Metadata::record(&new_version.to_key(), metadata);
+            }
+            if let Some(signature) = &signature {
+                Signatures::record(&new_version.to_key(), signature);
+            }
+        })
+    }
+
+    /// Delete the binaries of the listed `versions` and of releases older than `older_than`,
+    /// transferring the freed storage stake to the owner. Pruned versions stay in the
+    /// history, marked as pruned, but can't be fetched. The current version, i.e. the latest
+    /// one that wasn't yanked, and versions a channel points at can't be pruned.
+    #[payable]
+    pub fn prune(&mut self, older_than: Option<Version>, versions: Option<Vec<Version>>) {
+        Owner::assert_with_one_yocto();
+        let latest = self.latest().map(|version| version.to_key());
+        let statuses = Statuses::get_lazy().unwrap_or_default();
+        let mut to_prune = versions.unwrap_or_default();
+        if let Some(older_than) = older_than {
+            to_prune.extend(self.versions.iter().filter(|version| *version < older_than));
+        }
+        storage::refund_freed(|| {
+            for version in to_prune {
+                if statuses.is_pruned(&version.to_key()) {
+                    continue;
+                }
+                require!(
+                    latest != Some(version.to_key()),
+                    "Can't prune the current version"
+                );
+                require!(
+                    !Channels::points_at(&version),
+                    format!("Can't prune {version}, a channel points at it")
+                );
+                require!(
+                    Binaries::has_version(&version.to_key()),
+                    format!("Version {version} is not published")
+                );
+                Binaries::unlink(&version.to_key());
+                Signatures::remove(&version.to_key());
+                Statuses::mark_pruned(&version.to_key());
+            }
+        })
+    }
+}
 
-        // refund_storage_cost(|| {
-        let new_version = self.current().publish_patch();
-        self.versions.push(&new_version);
-        env::storage_write(&new_version.to_key(), &contract_bytes.to_vec());
-        // });
+/// Patch releases can be published by the owner or, with the `near-riffs-admins` feature,
+/// by an admin. Other releases are owner-only.
+pub(crate) fn assert_can_publish_patch() {
+    #[cfg(feature = "near-riffs-admins")]
+    near_riffs_admins::Admins::assert_owner_or_admin();
+    #[cfg(not(feature = "near-riffs-admins"))]
+    Owner::assert_owner();
+}
+
+/// Panics unless the wasm exports what's needed to redeploy it, or the publisher passed
+/// `terminal` to publish a contract that can't be upgraded again
+pub(crate) fn assert_redeployable(wasm: &[u8], terminal: Option<bool>) {
+    match wasm::validate(wasm) {
+        Ok(()) => {}
+        Err(WasmError::MissingExports(_)) if terminal == Some(true) => {}
+        Err(err @ WasmError::MissingExports(_)) => {
+            env::panic_str(&format!("{err}. Pass terminal: true to publish it anyway"))
+        }
+        Err(err) => env::panic_str(&err.to_string()),
     }
 }
 
+fn parse_version(version: &str) -> Version {
+    version
+        .parse()
+        .unwrap_or_else(|e| env::panic_str(&e.to_string()))
+}
+
+/// Read the bytes of a version into a register
+fn read_version(version: &Version) -> u64 {
+    Statuses::assert_not_pruned(&version.to_key());
+    Binaries::read_to_reg(&version.to_key()).expect("MISSING BINARY")
+}
+
 impl Registry {
     pub fn fetch_to_reg(&self) -> u64 {
-        reg::storage_read(&self.current().to_key()).expect("Currently no version available")
+        let latest = self
+            .latest()
+            .unwrap_or_else(|| env::panic_str("Currently no version available"));
+        read_version(&latest)
+    }
+
+    /// Read the bytes of a version, of a channel's version or of `package@version`
+    /// into a register. Panics if the version was yanked or pruned.
+    pub fn fetch_version_to_reg(&self, version_or_channel: &str) -> u64 {
+        self.version_to_reg(version_or_channel, false)
+    }
+
+    /// Like `fetch_version_to_reg`, but returns yanked versions when `force` is set
+    fn version_to_reg(&self, version_or_channel: &str, force: bool) -> u64 {
+        if version_or_channel.contains('@') {
+            return Packages::fetch_to_reg(version_or_channel, force);
+        }
+        let version = self.resolve(version_or_channel);
+        if !force {
+            Statuses::assert_not_yanked(&version.to_key());
+        }
+        read_version(&version)
+    }
+
+    /// Versions newest first, only those of `major` if given. Versions are published in
+    /// increasing order, so newer majors are skipped and older ones are never read.
+    fn newest_first(&self, major: Option<u16>) -> impl Iterator<Item = Version> + '_ {
+        self.versions
+            .iter()
+            .rev()
+            .skip_while(move |version| major.map_or(false, |major| version.major() > major))
+            .take_while(move |version| major.map_or(true, |major| version.major() == major))
+    }
+
+    /// Latest version that wasn't yanked or pruned, of `major` if given
+    fn latest_matching(&self, major: Option<u16>) -> Option<Version> {
+        let statuses = Statuses::get_lazy().unwrap_or_default();
+        self.newest_first(major).find(|version| {
+            let key = version.to_key();
+            !statuses.is_yanked(&key) && !statuses.is_pruned(&key)
+        })
+    }
+
+    /// Version `fetch` returns for the same input, as a storage key, e.g. `1_2_3` for `v1_2_3`,
+    /// a channel or no input, or `factory@1_2_3` for a package
+    pub fn resolve_key(&self, version_or_channel: &str) -> String {
+        String::from_utf8(self.binary_key(version_or_channel))
+            .unwrap_or_else(|_| env::panic_str("Version key is not utf-8"))
+    }
+
+    /// Storage key of the current version, a version, a channel's version or `package@version`
+    fn binary_key(&self, version_or_channel: &str) -> Vec<u8> {
+        if version_or_channel.is_empty() {
+            return self
+                .latest()
+                .unwrap_or_else(|| env::panic_str("Currently no version available"))
+                .to_key();
+        }
+        match version_or_channel.split_once('@') {
+            Some((package, version)) => packages::package_key(package, &parse_version(version)),
+            None => self.resolve(version_or_channel).to_key(),
+        }
+    }
+
+    fn resolve(&self, version_or_channel: &str) -> Version {
+        version_or_channel.parse::<Version>().unwrap_or_else(|_| {
+            Channels::version(version_or_channel).unwrap_or_else(|| {
+                env::panic_str(&format!("No version or channel {version_or_channel}"))
+            })
+        })
+    }
+}
+
+#[allow(dead_code, unused_variables)]
+mod private {
+    use near_riffs::{near_sdk::json_types::U64, witgen};
+
+    use crate::Release;
+
+    /// Published versions, newest first, starting at `from_index`.
+    /// Pass `major` to only list the versions of a major version, e.g. `1` for `v1_*`.
+    /// Without input the newest versions of all majors are listed.
+    #[witgen]
+    pub fn versions(
+        from_index: Option<U64>,
+        limit: Option<U64>,
+        major: Option<u16>,
+    ) -> Vec<String> {
+        vec![]
+    }
+
+    /// Releases with their metadata, oldest first, starting at `from_index`.
+    /// Without input the oldest releases are listed.
+    #[witgen]
+    pub fn release_history(from_index: Option<U64>, limit: Option<U64>) -> Vec<Release> {
+        vec![]
     }
 }
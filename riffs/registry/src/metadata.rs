@@ -0,0 +1,89 @@
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        collections::LookupMap,
+        near_bindgen, require,
+        serde::{Deserialize, Serialize},
+    },
+    prelude::*,
+    storage,
+};
+use near_riffs_core::Owner;
+
+use crate::{packages::version_key, Binaries, ReleaseStatus};
+
+pub const METADATA_KEY: &str = "RELEASE_METADATA";
+
+/// How a release was made, in the spirit of NEP-330 contract source metadata
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReleaseMetadata {
+    /// What changed since the previous release
+    pub changelog: Option<String>,
+    /// Link to the source repository, like NEP-330's `link`
+    pub repository: Option<String>,
+    /// Commit of the repository the release was built from
+    pub commit: Option<String>,
+    /// Command that reproduces the release's bytes, e.g. `cargo build --release`
+    pub build_command: Option<String>,
+}
+
+/// A published version with its metadata and whether it was yanked, deprecated or pruned
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Release {
+    pub version: String,
+    pub metadata: Option<ReleaseMetadata>,
+    pub status: Option<ReleaseStatus>,
+}
+
+/// Metadata of published versions, keyed by the version's storage key,
+/// e.g. `1_2_3` or `factory@1_2_3` for a package's version
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Metadata(LookupMap<Vec<u8>, ReleaseMetadata>);
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self(LookupMap::new(b"m".to_vec()))
+    }
+}
+
+impl IntoKey for Metadata {
+    fn into_storage_key() -> Vec<u8> {
+        METADATA_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Metadata {
+    /// Record the metadata of the version with storage key `key` being published
+    pub fn record(key: &[u8], metadata: &ReleaseMetadata) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        this.0.insert(&key.to_vec(), metadata);
+        Self::set_lazy(this);
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<ReleaseMetadata> {
+        self.0.get(&key.to_vec())
+    }
+}
+
+#[near_bindgen(riff)]
+impl Metadata {
+    /// Set or replace the metadata of a published version, e.g. one published with `patch`,
+    /// or of a package's version, e.g. `factory@0_0_1`
+    #[payable]
+    pub fn set_release_metadata(&mut self, version: String, metadata: ReleaseMetadata) {
+        Owner::assert_owner();
+        let key = version_key(&version);
+        require!(Binaries::has_version(&key), "Version is not published");
+        storage::refund_cost(|| {
+            self.0.insert(&key, &metadata);
+        })
+    }
+
+    pub fn release_metadata(&self, version: String) -> Option<ReleaseMetadata> {
+        self.get(&version_key(&version))
+    }
+}
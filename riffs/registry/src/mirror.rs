@@ -0,0 +1,235 @@
+//! # Mirroring
+//!
+//! Lets a registry copy versions from an upstream registry, so teams can keep independent
+//! mirrors and switch `redeploy` to one if the upstream account is lost or compromised.
+//! The owner sets the `upstream` registry and calls `mirror` with a version, which fetches the
+//! version's binary, code hash, metadata and signature from the upstream and stores them once
+//! the bytes match the code hash. Versions have to be mirrored in order, like they are published.
+use near_riffs::{
+    account::assert_private,
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env,
+        json_types::{Base58CryptoHash, U128},
+        near_bindgen,
+        serde::de::DeserializeOwned,
+        serde_json::{self, json},
+        AccountId, CryptoHash, Gas, GasWeight, PromiseResult,
+    },
+    near_units::parse_gas,
+    prelude::*,
+    signature::ReleaseSignature,
+    storage,
+    version::Version,
+};
+use near_riffs_core::Owner;
+
+use crate::{Binaries, Metadata, PreReleases, Registry, ReleaseMetadata, Signatures};
+
+pub const MIRROR_KEY: &str = "MIRROR";
+
+const FETCH_GAS: u64 = parse_gas!("50 Tgas") as u64;
+const VIEW_GAS: u64 = parse_gas!("10 Tgas") as u64;
+/// Least gas of `on_mirror`, which also gets all the unused prepaid gas, since hashing and
+/// storing a large binary takes more
+const ON_MIRROR_GAS: u64 = parse_gas!("100 Tgas") as u64;
+/// Bytes stored besides the binary and its metadata, e.g. storage keys and the signature,
+/// when checking that the deposit covers a mirrored version before storing it
+const MIRROR_OVERHEAD: u64 = 1_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct Mirror {
+    /// Registry versions are mirrored from
+    upstream: Option<AccountId>,
+}
+
+impl IntoKey for Mirror {
+    fn into_storage_key() -> Vec<u8> {
+        MIRROR_KEY.as_bytes().to_vec()
+    }
+}
+
+/// Whether the version can be added to the registry after its current version
+fn check_mirrorable(registry: &Registry, version: &Version) -> Result<(), &'static str> {
+    if Binaries::has_version(&version.to_key()) {
+        return Err("Version already published");
+    }
+    if *version <= registry.current() {
+        return Err("Versions must be mirrored in order, newer than the current version");
+    }
+    Ok(())
+}
+
+/// Panics unless the version can be added to the registry after its current version
+fn assert_mirrorable(registry: &Registry, version: &Version) {
+    if let Err(err) = check_mirrorable(registry, version) {
+        env::panic_str(err)
+    }
+}
+
+fn transfer(account_id: &AccountId, amount: u128) {
+    if 0 < amount {
+        let promise_index = env::promise_batch_create(account_id);
+        env::promise_batch_action_transfer(promise_index, amount);
+    }
+}
+
+/// JSON result of the promise at `index`, `None` if the promise failed
+fn promise_json<T: DeserializeOwned>(index: u64) -> Option<T> {
+    match env::promise_result(index) {
+        PromiseResult::Successful(value) => serde_json::from_slice(&value).ok(),
+        _ => None,
+    }
+}
+
+#[near_bindgen(riff)]
+impl Mirror {
+    #[payable]
+    pub fn set_upstream(&mut self, upstream: Option<AccountId>) {
+        Owner::assert_with_one_yocto();
+        self.upstream = upstream;
+    }
+
+    pub fn upstream(&self) -> Option<&AccountId> {
+        self.upstream.as_ref()
+    }
+
+    /// Copy a version from the upstream registry. Its bytes are checked against the upstream's
+    /// code hash of the version, which `code_hash` has to match if both are given, or against
+    /// `code_hash` for versions the upstream has no code hash for.
+    /// Attach enough to cover the storage of the binary, the rest is refunded once it's stored.
+    /// If mirroring fails the whole deposit is refunded. The unused prepaid gas goes to storing
+    /// the version, so attach more gas for large binaries.
+    #[payable]
+    pub fn mirror(&mut self, version: Version, code_hash: Option<Base58CryptoHash>) {
+        Owner::assert_owner();
+        let upstream = self
+            .upstream
+            .clone()
+            .unwrap_or_else(|| env::panic_str("No upstream registry to mirror"));
+        assert_mirrorable(&Registry::get_lazy().unwrap_or_default(), &version);
+        let key = version.to_key();
+        let args = json!({ "version": version.to_string() }).to_string();
+        let promises = [
+            env::promise_create(upstream.clone(), "fetch", &key, 0, Gas(FETCH_GAS)),
+            env::promise_create(
+                upstream.clone(),
+                "version_code_hash",
+                args.as_bytes(),
+                0,
+                Gas(VIEW_GAS),
+            ),
+            env::promise_create(
+                upstream.clone(),
+                "release_metadata",
+                args.as_bytes(),
+                0,
+                Gas(VIEW_GAS),
+            ),
+            env::promise_create(upstream, "fetch_signature", &key, 0, Gas(VIEW_GAS)),
+        ];
+        let callback_args = json!({
+            "version": version.to_string(),
+            "code_hash": code_hash,
+            "payer": env::predecessor_account_id(),
+            "deposit": U128(env::attached_deposit()),
+        })
+        .to_string();
+        let callback =
+            env::promise_batch_then(env::promise_and(&promises), &env::current_account_id());
+        env::promise_batch_action_function_call_weight(
+            callback,
+            "on_mirror",
+            callback_args.as_bytes(),
+            0,
+            Gas(ON_MIRROR_GAS),
+            GasWeight(1),
+        );
+        env::promise_return(callback)
+    }
+
+    /// Store the version fetched by `mirror`, refunding what's left of `deposit` to `payer`.
+    /// Returns whether the version was stored. Otherwise the reason is logged and the whole
+    /// deposit is refunded.
+    pub fn on_mirror(
+        &mut self,
+        version: Version,
+        code_hash: Option<Base58CryptoHash>,
+        payer: AccountId,
+        deposit: U128,
+    ) -> bool {
+        assert_private();
+        match store_mirrored(&version, code_hash, deposit.0) {
+            Ok(cost) => {
+                transfer(&payer, deposit.0.saturating_sub(cost));
+                true
+            }
+            Err(err) => {
+                env::log_str(err);
+                transfer(&payer, deposit.0);
+                false
+            }
+        }
+    }
+}
+
+/// Check and store the results of `mirror`'s promises, returning the storage cost.
+/// Nothing is stored unless every check passes, including that `deposit` covers the storage.
+fn store_mirrored(
+    version: &Version,
+    code_hash: Option<Base58CryptoHash>,
+    deposit: u128,
+) -> Result<u128, &'static str> {
+    let bytes = match env::promise_result(0) {
+        PromiseResult::Successful(bytes) => bytes,
+        _ => return Err("Upstream could not fetch the version"),
+    };
+    // The upstream's code hash is authoritative, the caller's only fills in when it has none
+    let upstream_hash = promise_json::<Option<Base58CryptoHash>>(1).flatten();
+    let expected = match (upstream_hash, code_hash) {
+        (Some(upstream), Some(code_hash)) if upstream != code_hash => {
+            return Err("code_hash does not match the upstream's code hash")
+        }
+        (upstream, code_hash) => upstream
+            .or(code_hash)
+            .ok_or("Upstream has no code hash for the version, pass code_hash")?,
+    };
+    let code_hash = env::sha256_array(&bytes);
+    if code_hash != CryptoHash::from(expected) {
+        return Err("Mirrored bytes do not match the code hash");
+    }
+    let metadata = promise_json::<Option<ReleaseMetadata>>(2).flatten();
+    // A signature is kept if it's valid, whether or not this registry trusts its key
+    let signature = promise_json::<Option<ReleaseSignature>>(3)
+        .flatten()
+        .filter(|signature| signature.verify(&code_hash));
+
+    let mut registry = Registry::get_lazy().unwrap_or_default();
+    check_mirrorable(&registry, version)?;
+    let metadata_len = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.try_to_vec().ok())
+        .map_or(0, |metadata| metadata.len());
+    let max_bytes = (bytes.len() + metadata_len) as u128 + MIRROR_OVERHEAD as u128;
+    if deposit < env::storage_byte_cost() * max_bytes {
+        return Err("Not enough attached deposit to cover storage");
+    }
+    let ((), cost) = storage::measure_cost(|| {
+        Binaries::store(&version.to_key(), &bytes);
+        if version.is_pre_release() {
+            PreReleases::push(version);
+        } else {
+            registry.versions.push(version);
+        }
+        if let Some(metadata) = &metadata {
+            Metadata::record(&version.to_key(), metadata);
+        }
+        if let Some(signature) = &signature {
+            Signatures::insert_mirrored(&version.to_key(), signature);
+        }
+    });
+    Registry::set_lazy(registry);
+    Ok(cost)
+}
@@ -0,0 +1,179 @@
+//! # Packages
+//!
+//! Lets one registry host many contracts. Each named package, e.g. `factory`, has its own
+//! owners and versions, and is fetched with `package@version`, e.g. `factory@1_2_3`.
+use near_riffs::{
+    input::DataUrl,
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        collections::UnorderedMap,
+        env, near_bindgen, require,
+        serde::Serialize,
+        AccountId,
+    },
+    prelude::*,
+    signature::ReleaseSignature,
+    storage,
+    version::Version,
+};
+use near_riffs_core::Owner;
+
+use crate::{assert_redeployable, parse_version, Binaries, ReleaseKind, Signatures, Statuses};
+
+pub const PACKAGES_KEY: &str = "PACKAGES";
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Package {
+    /// Accounts that can publish the package and change its owners
+    pub owners: Vec<AccountId>,
+    /// Published versions, oldest first
+    pub versions: Vec<String>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Packages(UnorderedMap<String, Package>);
+
+impl Default for Packages {
+    fn default() -> Self {
+        Self(UnorderedMap::new(b"p".to_vec()))
+    }
+}
+
+impl IntoKey for Packages {
+    fn into_storage_key() -> Vec<u8> {
+        PACKAGES_KEY.as_bytes().to_vec()
+    }
+}
+
+/// Storage key of a package's version, e.g. `factory@1_2_3`
+pub(crate) fn package_key(package: &str, version: &Version) -> Vec<u8> {
+    [package.as_bytes(), b"@", &version.to_key()].concat()
+}
+
+/// Storage key of a version, e.g. `1_2_3` for `v1_2_3`, or of a package's version,
+/// e.g. `factory@1_2_3` for `factory@v1_2_3`
+pub(crate) fn version_key(version: &str) -> Vec<u8> {
+    match version.split_once('@') {
+        Some((package, version)) => package_key(package, &parse_version(version)),
+        None => parse_version(version).to_key(),
+    }
+}
+
+impl Packages {
+    /// Read the binary of `package@version` into a register. Panics if it was pruned,
+    /// or yanked unless `force` is set.
+    pub fn fetch_to_reg(package_and_version: &str, force: bool) -> u64 {
+        let (package, version) = package_and_version
+            .split_once('@')
+            .unwrap_or_else(|| env::panic_str("Expected package@version"));
+        let version = parse_version(version);
+        let this = Self::get_lazy().unwrap_or_default();
+        require!(
+            this.0
+                .get(&package.to_string())
+                .map_or(false, |package| package
+                    .versions
+                    .contains(&version.to_string())),
+            "No such package version"
+        );
+        let key = package_key(package, &version);
+        if !force {
+            Statuses::assert_not_yanked(&key);
+        }
+        Statuses::assert_not_pruned(&key);
+        Binaries::read_to_reg(&key).expect("MISSING BINARY")
+    }
+
+    fn package(&self, package: &str) -> Package {
+        self.0
+            .get(&package.to_string())
+            .unwrap_or_else(|| env::panic_str("No such package"))
+    }
+
+    fn assert_package_owner(package: &Package) {
+        require!(
+            package.owners.contains(&env::predecessor_account_id()),
+            "Not allowed: must be a package owner"
+        );
+    }
+}
+
+#[near_bindgen(riff)]
+impl Packages {
+    /// Add a package, which its owners can then publish. Only the registry's owner can add packages.
+    #[payable]
+    pub fn create_package(&mut self, package: String, owners: Vec<AccountId>) {
+        Owner::assert_owner();
+        require!(
+            !package.is_empty()
+                && package
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'),
+            "Package name can only contain lowercase letters, digits, - and _"
+        );
+        require!(self.0.get(&package).is_none(), "Package already exists");
+        storage::refund_cost(|| {
+            self.0.insert(
+                &package,
+                &Package {
+                    owners,
+                    versions: vec![],
+                },
+            );
+        })
+    }
+
+    #[payable]
+    pub fn set_package_owners(&mut self, package: String, owners: Vec<AccountId>) {
+        let mut current = self.package(&package);
+        Self::assert_package_owner(&current);
+        require!(!owners.is_empty(), "Package needs at least one owner");
+        current.owners = owners;
+        storage::refund_cost(|| {
+            self.0.insert(&package, &current);
+        })
+    }
+
+    /// Publish the next patch, minor or major version of a package, optionally with
+    /// a release key's `signature` of its code hash. Returns the new version.
+    #[payable]
+    pub fn publish_package(
+        &mut self,
+        package: String,
+        kind: ReleaseKind,
+        contract_bytes: DataUrl,
+        terminal: Option<bool>,
+        signature: Option<ReleaseSignature>,
+    ) -> String {
+        let mut current = self.package(&package);
+        Self::assert_package_owner(&current);
+        let contract_bytes = contract_bytes.to_vec();
+        assert_redeployable(&contract_bytes, terminal);
+        let latest = current
+            .versions
+            .last()
+            .map_or_else(Version::default, |version| parse_version(version));
+        let version = kind.next(latest);
+        current.versions.push(version.to_string());
+        storage::refund_cost(|| {
+            let key = package_key(&package, &version);
+            Binaries::store(&key, &contract_bytes);
+            self.0.insert(&package, &current);
+            if let Some(signature) = &signature {
+                Signatures::record(&key, signature);
+            }
+        });
+        version.to_string()
+    }
+
+    pub fn packages(&self) -> Vec<String> {
+        self.0.keys().collect()
+    }
+
+    pub fn get_package(&self, package: String) -> Option<Package> {
+        self.0.get(&package)
+    }
+}
@@ -0,0 +1,130 @@
+//! # Release signatures
+//!
+//! The owner registers release keys, e.g. one held by CI, whose ed25519 signatures over a
+//! version's code hash are stored with the version, or with a package's version. A bootloader
+//! that trusts a release key can then refuse code the key didn't sign.
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        collections::LookupMap,
+        env, near_bindgen, require, PublicKey,
+    },
+    prelude::*,
+    signature::ReleaseSignature,
+    storage,
+};
+use near_riffs_core::Owner;
+
+use crate::{packages::version_key, Binaries};
+
+pub const SIGNATURES_KEY: &str = "RELEASE_SIGNATURES";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Signatures {
+    /// Keys whose signatures are accepted
+    release_keys: Vec<PublicKey>,
+    /// Signature of each version, keyed by the version's storage key
+    signatures: LookupMap<Vec<u8>, ReleaseSignature>,
+}
+
+impl Default for Signatures {
+    fn default() -> Self {
+        Self {
+            release_keys: vec![],
+            signatures: LookupMap::new(b"g".to_vec()),
+        }
+    }
+}
+
+impl IntoKey for Signatures {
+    fn into_storage_key() -> Vec<u8> {
+        SIGNATURES_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Signatures {
+    /// Store the signature of the version with storage key `key`, panicking unless
+    /// a release key signed the version's code hash
+    pub fn record(key: &[u8], signature: &ReleaseSignature) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        this.insert(key, signature);
+        Self::set_lazy(this);
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<ReleaseSignature> {
+        self.signatures.get(&key.to_vec())
+    }
+
+    /// Store a signature mirrored from another registry, which was checked against the
+    /// code hash but may be from a key this registry doesn't accept
+    pub(crate) fn insert_mirrored(key: &[u8], signature: &ReleaseSignature) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        this.signatures.insert(&key.to_vec(), signature);
+        Self::set_lazy(this);
+    }
+
+    /// Give the version with storage key `to` the signature of `from`, which has the same binary
+    pub(crate) fn copy(from: &[u8], to: &[u8]) {
+        if let Some(mut this) = Self::get_lazy() {
+            if let Some(signature) = this.get(from) {
+                this.signatures.insert(&to.to_vec(), &signature);
+            }
+        }
+    }
+
+    pub(crate) fn remove(key: &[u8]) {
+        if let Some(mut this) = Self::get_lazy() {
+            this.signatures.remove(&key.to_vec());
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], signature: &ReleaseSignature) {
+        require!(
+            self.release_keys.contains(&signature.public_key),
+            "Not a release key"
+        );
+        let code_hash =
+            Binaries::code_hash(key).unwrap_or_else(|| env::panic_str("Version is not published"));
+        require!(
+            signature.verify(&code_hash),
+            "Invalid signature of the version's code hash"
+        );
+        self.signatures.insert(&key.to_vec(), signature);
+    }
+}
+
+#[near_bindgen(riff)]
+impl Signatures {
+    #[payable]
+    pub fn add_release_key(&mut self, public_key: PublicKey) {
+        Owner::assert_with_one_yocto();
+        if !self.release_keys.contains(&public_key) {
+            self.release_keys.push(public_key);
+        }
+    }
+
+    /// Stop accepting new signatures from a key. Signatures already stored are kept.
+    #[payable]
+    pub fn remove_release_key(&mut self, public_key: PublicKey) {
+        Owner::assert_with_one_yocto();
+        self.release_keys.retain(|key| *key != public_key);
+    }
+
+    pub fn release_keys(&self) -> &Vec<PublicKey> {
+        &self.release_keys
+    }
+
+    /// Add the signature of a published version, e.g. `0_0_1`, or of a package's version,
+    /// e.g. `factory@0_0_1`. Anyone can submit it, since it has to be made with a release key.
+    #[payable]
+    pub fn sign_release(&mut self, version: String, signature: ReleaseSignature) {
+        storage::refund_cost(|| self.insert(&version_key(&version), &signature))
+    }
+
+    /// Signature of a version or of a package's version, e.g. `factory@0_0_1`
+    pub fn release_signature(&self, version: String) -> Option<ReleaseSignature> {
+        self.get(&version_key(&version))
+    }
+}
@@ -0,0 +1,117 @@
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        collections::LookupMap,
+        near_bindgen, require,
+        serde::{Deserialize, Serialize},
+    },
+    prelude::*,
+};
+use near_riffs_core::Owner;
+
+use crate::{packages::version_key, Binaries};
+
+pub const STATUS_KEY: &str = "RELEASE_STATUS";
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum ReleaseStatus {
+    /// Still fetched as usual, but flagged so it isn't picked for new deploys
+    Deprecated,
+    /// Only fetched when forced and never the current version
+    Yanked,
+    /// Binary was deleted to reclaim storage, so it can't be fetched anymore
+    Pruned,
+}
+
+/// Status of versions that were yanked, deprecated or pruned, keyed by the version's storage key,
+/// e.g. `1_2_3` or `factory@1_2_3` for a package's version
+#[derive(BorshSerialize, BorshDeserialize)]
+#[near_bindgen(riff)]
+pub struct Statuses(LookupMap<Vec<u8>, ReleaseStatus>);
+
+impl Default for Statuses {
+    fn default() -> Self {
+        Self(LookupMap::new(b"s".to_vec()))
+    }
+}
+
+impl IntoKey for Statuses {
+    fn into_storage_key() -> Vec<u8> {
+        STATUS_KEY.as_bytes().to_vec()
+    }
+}
+
+impl Statuses {
+    pub fn get(&self, key: &[u8]) -> Option<ReleaseStatus> {
+        self.0.get(&key.to_vec())
+    }
+
+    pub fn is_yanked(&self, key: &[u8]) -> bool {
+        self.get(key) == Some(ReleaseStatus::Yanked)
+    }
+
+    pub fn is_pruned(&self, key: &[u8]) -> bool {
+        self.get(key) == Some(ReleaseStatus::Pruned)
+    }
+
+    pub fn assert_not_yanked(key: &[u8]) {
+        require!(
+            !Self::get_lazy().map_or(false, |this| this.is_yanked(key)),
+            "Version was yanked"
+        );
+    }
+
+    /// Panics if the version's binary was pruned
+    pub fn assert_not_pruned(key: &[u8]) {
+        require!(
+            !Self::get_lazy().map_or(false, |this| this.is_pruned(key)),
+            "Version was pruned, its binary is no longer stored"
+        );
+    }
+
+    /// Mark a version as pruned, which is permanent
+    pub(crate) fn mark_pruned(key: &[u8]) {
+        let mut this = Self::get_lazy().unwrap_or_default();
+        this.0.insert(&key.to_vec(), &ReleaseStatus::Pruned);
+        Self::set_lazy(this);
+    }
+
+    fn set(&mut self, version: &str, status: Option<ReleaseStatus>) {
+        Owner::assert_with_one_yocto();
+        let key = version_key(version);
+        require!(!self.is_pruned(&key), "Version was pruned");
+        require!(Binaries::has_version(&key), "Version is not published");
+        match status {
+            Some(status) => self.0.insert(&key, &status),
+            None => self.0.remove(&key),
+        };
+    }
+}
+
+#[near_bindgen(riff)]
+impl Statuses {
+    /// Stop serving a version, e.g. `0_0_1`, or a package's version, e.g. `factory@0_0_1`,
+    /// unless `fetch` is forced
+    #[payable]
+    pub fn yank(&mut self, version: String) {
+        self.set(&version, Some(ReleaseStatus::Yanked));
+    }
+
+    /// Flag a version as deprecated, while still serving it
+    #[payable]
+    pub fn deprecate(&mut self, version: String) {
+        self.set(&version, Some(ReleaseStatus::Deprecated));
+    }
+
+    /// Undo yanking or deprecating a version
+    #[payable]
+    pub fn restore(&mut self, version: String) {
+        self.set(&version, None);
+    }
+
+    pub fn release_status(&self, version: String) -> Option<ReleaseStatus> {
+        self.get(&version_key(&version))
+    }
+}
@@ -0,0 +1,201 @@
+//! # Chunked upload
+//!
+//! Publishes contracts too large for a single transaction. The owner calls `start_upload`,
+//! then `append_chunk` with consecutive chunks of the contract, and finally `finalize_upload`
+//! with the sha256 hash of the whole contract to publish it. `abort_upload` discards the chunks.
+//! Anyone who can publish a patch release can upload, but only the owner can finalize
+//! a minor or major release. Only the uploader can add chunks and finalize, and the stake
+//! of aborted chunks is refunded to the uploader, who paid for them.
+use near_riffs::{
+    near_sdk::{
+        self,
+        borsh::{self, BorshDeserialize, BorshSerialize},
+        env,
+        json_types::{Base58CryptoHash, Base64VecU8, U64},
+        near_bindgen, require,
+        serde::{Deserialize, Serialize},
+        AccountId, CryptoHash,
+    },
+    prelude::*,
+    signature::ReleaseSignature,
+    storage,
+    version::Version,
+};
+use near_riffs_core::Owner;
+
+use crate::{
+    assert_can_publish_patch, assert_redeployable, Binaries, Metadata, Registry, ReleaseMetadata,
+    Signatures,
+};
+
+pub const UPLOAD_KEY: &str = "UPLOAD";
+
+/// Which part of the version a finalized upload increases
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum ReleaseKind {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl ReleaseKind {
+    /// Version published after `current`
+    pub fn next(self, current: Version) -> Version {
+        match self {
+            ReleaseKind::Patch => current.publish_patch(),
+            ReleaseKind::Minor => current.publish_minor(),
+            ReleaseKind::Major => current.publish_major(),
+        }
+    }
+}
+
+/// Upload in progress
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UploadSession {
+    /// Account that started the upload and pays for its chunks
+    pub uploader: AccountId,
+    /// Bytes uploaded so far, i.e. the offset of the next chunk
+    pub len: U64,
+    pub chunks: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+#[near_bindgen(riff)]
+pub struct Upload(Option<UploadSession>);
+
+impl IntoKey for Upload {
+    fn into_storage_key() -> Vec<u8> {
+        UPLOAD_KEY.as_bytes().to_vec()
+    }
+}
+
+fn chunk_key(index: u32) -> Vec<u8> {
+    format!("{UPLOAD_KEY}:{index}").into_bytes()
+}
+
+impl Upload {
+    fn session(&mut self) -> &mut UploadSession {
+        self.0
+            .as_mut()
+            .unwrap_or_else(|| env::panic_str("No upload in progress"))
+    }
+
+    /// Session of the current upload, which must have been started by the predecessor
+    fn own_session(&mut self) -> &mut UploadSession {
+        let session = self.session();
+        require!(
+            session.uploader == env::predecessor_account_id(),
+            "Only the uploader can continue the upload"
+        );
+        session
+    }
+
+    /// Remove the session's chunks, returning their bytes in order
+    fn take_chunks(&mut self) -> Vec<u8> {
+        let session = self
+            .0
+            .take()
+            .unwrap_or_else(|| env::panic_str("No upload in progress"));
+        let mut bytes = Vec::with_capacity(session.len.0 as usize);
+        for index in 0..session.chunks {
+            let key = chunk_key(index);
+            bytes
+                .extend(env::storage_read(&key).unwrap_or_else(|| env::panic_str("MISSING CHUNK")));
+            env::storage_remove(&key);
+        }
+        bytes
+    }
+}
+
+#[near_bindgen(riff)]
+impl Upload {
+    pub fn start_upload(&mut self) {
+        assert_can_publish_patch();
+        require!(
+            self.0.is_none(),
+            "Upload already in progress, finalize or abort it first"
+        );
+        self.0 = Some(UploadSession {
+            uploader: env::predecessor_account_id(),
+            len: U64(0),
+            chunks: 0,
+        });
+    }
+
+    /// Append the chunk starting at `offset`, which must be the number of bytes uploaded so far.
+    /// Attach a deposit to cover storing the chunk.
+    #[payable]
+    pub fn append_chunk(&mut self, offset: U64, chunk: Base64VecU8) {
+        assert_can_publish_patch();
+        let session = self.own_session();
+        require!(
+            offset == session.len,
+            format!("Expected chunk at offset {}", session.len.0)
+        );
+        storage::refund_cost(|| env::storage_write(&chunk_key(session.chunks), &chunk.0));
+        session.len = (session.len.0 + chunk.0.len() as u64).into();
+        session.chunks += 1;
+    }
+
+    /// Publish the uploaded contract as a new patch, minor or major version, checking it
+    /// against its sha256 `code_hash`, optionally with a release key's `signature` of it.
+    /// Returns the new version.
+    #[payable]
+    pub fn finalize_upload(
+        &mut self,
+        code_hash: Base58CryptoHash,
+        kind: ReleaseKind,
+        metadata: Option<ReleaseMetadata>,
+        terminal: Option<bool>,
+        signature: Option<ReleaseSignature>,
+    ) -> String {
+        match kind {
+            ReleaseKind::Patch => assert_can_publish_patch(),
+            _ => Owner::assert_owner(),
+        }
+        let uploader = self.own_session().uploader.clone();
+        let mut registry = Registry::get_lazy().unwrap_or_default();
+        let version = kind.next(registry.current());
+        // The freed chunks offset the stored binary, so the uploader only pays the difference,
+        // or gets back what's left when the binary was already stored
+        storage::refund_cost(|| {
+            storage::refund_freed_to(&uploader, || {
+                let bytes = self.take_chunks();
+                require!(
+                    env::sha256_array(&bytes) == CryptoHash::from(code_hash),
+                    "Uploaded bytes do not match code_hash"
+                );
+                assert_redeployable(&bytes, terminal);
+                Binaries::store(&version.to_key(), &bytes);
+                registry.versions.push(&version);
+                if let Some(metadata) = &metadata {
+                    Metadata::record(&version.to_key(), metadata);
+                }
+                if let Some(signature) = &signature {
+                    Signatures::record(&version.to_key(), signature);
+                }
+            })
+        });
+        Registry::set_lazy(registry);
+        version.to_string()
+    }
+
+    /// Discard the uploaded chunks, refunding their storage to the uploader.
+    /// Can be called by the uploader or the owner.
+    #[payable]
+    pub fn abort_upload(&mut self) {
+        let uploader = self.session().uploader.clone();
+        if uploader != env::predecessor_account_id() {
+            Owner::assert_owner();
+        }
+        storage::refund_freed_to(&uploader, || {
+            self.take_chunks();
+        })
+    }
+
+    pub fn upload_status(&self) -> Option<&UploadSession> {
+        self.0.as_ref()
+    }
+}
@@ -30,6 +30,86 @@ pub fn parse_json_or_string(input: &str, key: &str) -> Result<String, microjson:
     }
 }
 
+/// Reads the string value of `key` if `input` is a JSON object containing it.
+/// Panics if the value is neither a string nor `null`.
+pub fn json_key(input: &str, key: &str) -> Option<String> {
+    let object = JSONValue::parse(input).ok()?;
+    if !matches!(object.value_type, JSONValueType::Object) {
+        return None;
+    }
+    let value = object.get_key_value(key).ok()?;
+    if matches!(value.value_type, JSONValueType::Null) {
+        return None;
+    }
+    let value = value
+        .read_string()
+        .unwrap_or_else(|_| env::panic_str(&format!("{key} must be a string")));
+    Some(value.to_string())
+}
+
+/// Reads the boolean value of `key` if `input` is a JSON object containing it.
+/// Panics if the value is neither a boolean nor `null`.
+pub fn json_bool(input: &str, key: &str) -> Option<bool> {
+    let object = JSONValue::parse(input).ok()?;
+    if !matches!(object.value_type, JSONValueType::Object) {
+        return None;
+    }
+    let value = object.get_key_value(key).ok()?;
+    if matches!(value.value_type, JSONValueType::Null) {
+        return None;
+    }
+    let value = value
+        .read_boolean()
+        .unwrap_or_else(|_| env::panic_str(&format!("{key} must be a boolean")));
+    Some(value)
+}
+
+/// Reads the `u64` value of `key` if `input` is a JSON object containing it, given either
+/// as a number or as a string, since large numbers are often sent as strings.
+/// Panics if the value is neither a non-negative integer nor `null`.
+pub fn json_u64(input: &str, key: &str) -> Option<u64> {
+    let object = JSONValue::parse(input).ok()?;
+    if !matches!(object.value_type, JSONValueType::Object) {
+        return None;
+    }
+    let value = object.get_key_value(key).ok()?;
+    let digits = match value.value_type {
+        JSONValueType::Null => return None,
+        JSONValueType::String => value.read_string().ok(),
+        // microjson reads numbers as `isize`, which overflows on wasm32
+        JSONValueType::Number => number_after_key(input, key),
+        _ => None,
+    };
+    let value = digits
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or_else(|| env::panic_str(&format!("{key} must be a non-negative integer")));
+    Some(value)
+}
+
+/// Raw digits of the integer following `"key":` in `input`, `None` if the number isn't
+/// a non-negative integer, e.g. `1e14` or `5.5`
+fn number_after_key<'a>(input: &'a str, key: &str) -> Option<&'a str> {
+    let quoted_key = format!("\"{key}\"");
+    input
+        .match_indices(&quoted_key)
+        .find_map(|(index, _)| {
+            let rest = input[index + quoted_key.len()..]
+                .trim_start()
+                .strip_prefix(':')?
+                .trim_start();
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (digits, after) = rest.split_at(end);
+            let delimited = after
+                .chars()
+                .next()
+                .map_or(true, |c| c == ',' || c == '}' || c.is_whitespace());
+            Some((!digits.is_empty() && delimited).then_some(digits))
+        })
+        .flatten()
+}
+
 #[allow(dead_code)]
 mod p {
     use witgen::witgen;
@@ -59,3 +139,29 @@ impl From<DataUrl> for Vec<u8> {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_integers_as_numbers_or_strings() {
+        let input = r#"{"fetch_gas": 50000000000000, "deploy_gas": "100000000000000"}"#;
+        assert_eq!(json_u64(input, "fetch_gas"), Some(50_000_000_000_000));
+        assert_eq!(json_u64(input, "deploy_gas"), Some(100_000_000_000_000));
+        assert_eq!(json_u64(input, "migrate"), None);
+        assert_eq!(json_u64(r#"{"fetch_gas":7}"#, "fetch_gas"), Some(7));
+    }
+
+    #[test]
+    fn rejects_numbers_that_are_not_integers() {
+        for number in ["1e14", "5.5e13", "5.5", "1E3", "-1"] {
+            let input = format!(r#"{{"fetch_gas": {number}, "deploy_gas": "1"}}"#);
+            assert_eq!(number_after_key(&input, "fetch_gas"), None, "{number}");
+        }
+        assert_eq!(
+            number_after_key(r#"{"fetch_gas": 42 }"#, "fetch_gas"),
+            Some("42")
+        );
+    }
+}
@@ -11,8 +11,10 @@ pub mod input;
 pub mod lazy;
 pub mod promise;
 pub mod reg;
+pub mod signature;
 pub mod storage;
 pub mod version;
+pub mod wasm;
 
 pub use lazy::IntoKey;
 
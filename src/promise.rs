@@ -1,6 +1,6 @@
-use near_sdk::{env, sys, Balance, Gas};
+use near_sdk::{env, sys, Balance, Gas, PromiseResult};
 
-use crate::account::FixedAccountId;
+use crate::{account::FixedAccountId, reg};
 
 pub fn promise_then(
     promise_idx: u64,
@@ -74,6 +74,33 @@ pub fn promise_batch_action_function_call_fetch(
     }
 }
 
+/// Attach a callback on the current account, which is passed the current input as its arguments
+pub fn promise_then_for_current_with_input(
+    promise_index: u64,
+    function_name: &str,
+    amount: Balance,
+    gas: Gas,
+) -> u64 {
+    unsafe {
+        sys::promise_then(
+            promise_index,
+            u64::MAX,
+            reg::current_account_id(),
+            function_name.len() as _,
+            function_name.as_ptr() as _,
+            u64::MAX,
+            reg::input(),
+            &amount as *const Balance as _,
+            gas.0,
+        )
+    }
+}
+
+/// Whether the promise at `index` returned JSON `true`
+pub fn promise_result_is_true(index: u64) -> bool {
+    matches!(env::promise_result(index), PromiseResult::Successful(value) if value == b"true")
+}
+
 pub fn promise_result() -> u64 {
     match unsafe { sys::promise_result(0, 1) } {
         1 => 1,
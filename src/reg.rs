@@ -1,7 +1,8 @@
-use near_sdk::{env, require, sys};
+use near_sdk::{env, require, sys, CryptoHash};
 
 const EVICTED: u64 = u64::MAX - 1;
 // const DATA: u64 = u64::MAX - 2;
+const SHA256: u64 = u64::MAX - 3;
 pub enum Registers {
     Input = 0,
     CurrentAccountId = 1,
@@ -76,6 +77,14 @@ pub fn sha256_hash(input_reg: u64, output_reg: u64) {
     unsafe { sys::sha256(u64::MAX, input_reg, output_reg) }
 }
 
+/// Hash the contents of a register without copying them into memory
+pub fn sha256(input_reg: u64) -> CryptoHash {
+    sha256_hash(input_reg, SHA256);
+    let mut hash = [0u8; 32];
+    unsafe { sys::read_register(SHA256, hash.as_mut_ptr() as _) };
+    hash
+}
+
 pub fn value_return(input_reg: u64) {
     unsafe { sys::value_return(u64::MAX, input_reg) }
 }
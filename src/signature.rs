@@ -0,0 +1,48 @@
+//! ed25519 signatures over the sha256 code hash of a release, made with a release key,
+//! e.g. one held by CI. Registries store them with each version and the Redeployer can
+//! require one before deploying.
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    json_types::Base64VecU8,
+    serde::{Deserialize, Serialize},
+    sys, CryptoHash, CurveType, PublicKey,
+};
+
+/// Length of an ed25519 signature
+const SIGNATURE_LEN: usize = 64;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReleaseSignature {
+    /// Release key that signed, e.g. `ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp`
+    pub public_key: PublicKey,
+    /// Signature of the 32 bytes of the code hash
+    pub signature: Base64VecU8,
+}
+
+impl ReleaseSignature {
+    /// Whether this is a valid ed25519 signature of `code_hash`
+    pub fn verify(&self, code_hash: &CryptoHash) -> bool {
+        self.public_key.curve_type() == CurveType::ED25519
+            && self.signature.0.len() == SIGNATURE_LEN
+            && ed25519_verify(
+                &self.signature.0,
+                code_hash,
+                &self.public_key.as_bytes()[1..],
+            )
+    }
+}
+
+/// Verify an ed25519 `signature` of `message` with the 32 bytes of `public_key`
+pub fn ed25519_verify(signature: &[u8], message: &[u8], public_key: &[u8]) -> bool {
+    unsafe {
+        sys::ed25519_verify(
+            signature.len() as _,
+            signature.as_ptr() as _,
+            message.len() as _,
+            message.as_ptr() as _,
+            public_key.len() as _,
+            public_key.as_ptr() as _,
+        ) == 1
+    }
+}
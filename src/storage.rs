@@ -1,4 +1,4 @@
-use near_sdk::{env, require};
+use near_sdk::{env, require, AccountId};
 
 use crate::reg;
 
@@ -6,7 +6,8 @@ use crate::reg;
 pub fn measure_cost<O, F: FnOnce() -> O>(f: F) -> (O, u128) {
     let bytes_used_before = env::storage_usage();
     let res = f();
-    let bytes_used = env::storage_usage() - bytes_used_before;
+    // Freeing storage costs nothing
+    let bytes_used = env::storage_usage().saturating_sub(bytes_used_before);
     (res, env::storage_byte_cost() * bytes_used as u128)
 }
 
@@ -35,4 +36,23 @@ pub fn refund_cost<O, F: FnOnce() -> O>(f: F) -> O {
         env::promise_batch_action_transfer(promise_index, amount_to_refund);
     }
     res
-}
\ No newline at end of file
+}
+
+/// Excute function f, then transfer the storage stake freed by f to predecessor.
+pub fn refund_freed<O, F: FnOnce() -> O>(f: F) -> O {
+    refund_freed_to(&env::predecessor_account_id(), f)
+}
+
+/// Excute function f, then transfer the storage stake freed by f to `account_id`,
+/// e.g. the account that paid for the storage in the first place.
+pub fn refund_freed_to<O, F: FnOnce() -> O>(account_id: &AccountId, f: F) -> O {
+    let bytes_used_before = env::storage_usage();
+    let res = f();
+    let bytes_freed = bytes_used_before.saturating_sub(env::storage_usage());
+    let amount_to_refund = env::storage_byte_cost() * bytes_freed as u128;
+    if 0 < amount_to_refund {
+        let promise_index = env::promise_batch_create(account_id);
+        env::promise_batch_action_transfer(promise_index, amount_to_refund);
+    }
+    res
+}
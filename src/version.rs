@@ -1,26 +1,37 @@
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
 use crate::{
     near_sdk::{
         borsh::{self, BorshDeserialize, BorshSerialize},
-        serde::Serialize,
+        serde::{de, Deserialize, Deserializer, Serialize},
     },
     reg,
 };
 
-/// Represents the version of the contract
-#[derive(BorshSerialize, BorshDeserialize, Default)]
+/// Represents the version of the contract.
+/// The Borsh layout only stores the release, `patch`, `minor` and `major`, so pre-releases
+/// have to be stored as strings, like `PreReleases` in the registry does.
+#[derive(BorshSerialize, BorshDeserialize, Default, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Version {
     patch: u16,
     minor: u16,
     major: u16,
+    /// Pre-release tag, e.g. `rc.1` in `1.3.0-rc.1`
+    #[borsh_skip]
+    pre: Option<String>,
+    /// Build metadata, e.g. `abc123` in `1.3.0+abc123`. Not part of the storage key.
+    #[borsh_skip]
+    build: Option<String>,
 }
 
 impl From<&Version> for Vec<u8> {
     fn from(value: &Version) -> Vec<u8> {
-        format!("{}_{}_{}", value.major, value.minor, value.patch)
-            .as_bytes()
-            .to_vec()
+        let mut key = format!("{}_{}_{}", value.major, value.minor, value.patch);
+        if let Some(pre) = &value.pre {
+            key.push('-');
+            key.push_str(pre);
+        }
+        key.into_bytes()
     }
 }
 
@@ -33,28 +44,188 @@ impl Serialize for Version {
     }
 }
 
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String as Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
 impl Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "v{}_{}_{}", self.major, self.minor, self.patch)
+        write!(f, "v{}_{}_{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{build}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when a string isn't a version like `1.2.3`, `v1_2_3`, `1_2_3` or `1.3.0-rc.1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(String);
+
+impl Display for ParseVersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid version `{}`, expected e.g. 1.2.3 or v1_2_3",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    /// Parses `1.2.3`, `v1.2.3`, `v1_2_3` or `1_2_3`, optionally followed by a
+    /// pre-release tag and build metadata, e.g. `1.3.0-rc.1+abc123`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+        let version = s.strip_prefix('v').unwrap_or(s);
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (version, None),
+        };
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (version, Some(pre)),
+            None => (version, None),
+        };
+        let separator = if version.contains('.') { '.' } else { '_' };
+        let mut parts = version.split(separator).map(str::parse::<u16>);
+        let mut version = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => {
+                Version::new(major, minor, patch)
+            }
+            _ => return Err(err()),
+        };
+        if !pre.map_or(true, is_valid_tag) || !build.map_or(true, is_valid_tag) {
+            return Err(err());
+        }
+        version.pre = pre.map(ToString::to_string);
+        version.build = build.map(ToString::to_string);
+        Ok(version)
+    }
+}
+
+/// Dot separated identifiers of ASCII alphanumerics and hyphens, e.g. `rc.1`
+fn is_valid_tag(tag: &str) -> bool {
+    tag.split('.').all(|identifier| {
+        !identifier.is_empty()
+            && identifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+/// Semver precedence of pre-release tags: identifiers are compared in turn, numerically if
+/// both are numbers, with numbers before alphanumerics, and a longer tag wins a tie
+fn cmp_pre(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+impl Ord for Version {
+    /// Compares major, then minor, then patch. A pre-release comes before its release,
+    /// e.g. `1.3.0-rc.1 < 1.3.0`. Build metadata only breaks ties.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => cmp_pre(a, b),
+            })
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Version {
-    pub fn publish_patch(mut self) -> Self {
-        self.patch += 1;
-        self
+    pub fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            patch,
+            minor,
+            major,
+            pre: None,
+            build: None,
+        }
+    }
+
+    pub fn major(&self) -> u16 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u16 {
+        self.minor
+    }
+
+    pub fn patch(&self) -> u16 {
+        self.patch
+    }
+
+    pub fn pre(&self) -> Option<&str> {
+        self.pre.as_deref()
+    }
+
+    pub fn build(&self) -> Option<&str> {
+        self.build.as_deref()
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        self.pre.is_some()
+    }
+
+    /// The release this pre-release leads up to, e.g. `1.3.0` for `1.3.0-rc.1`
+    pub fn to_release(&self) -> Version {
+        Version::new(self.major, self.minor, self.patch)
+    }
+
+    /// Whether both versions share a major version, i.e. neither has breaking changes
+    /// relative to the other
+    pub fn same_major(&self, other: &Version) -> bool {
+        self.major == other.major
+    }
+
+    pub fn publish_patch(self) -> Self {
+        Version::new(self.major, self.minor, self.patch + 1)
     }
 
-    pub fn publish_minor(mut self) -> Self {
-        self.minor += 1;
-        self.patch = 0;
-        self
+    pub fn publish_minor(self) -> Self {
+        Version::new(self.major, self.minor + 1, 0)
     }
-    pub fn publish_major(mut self) -> Self {
-        self.major += 1;
-        self.minor = 0;
-        self.patch = 0;
-        self
+    pub fn publish_major(self) -> Self {
+        Version::new(self.major + 1, 0, 0)
     }
 
     pub fn input_to_storage(&self) {
@@ -66,3 +237,180 @@ impl Version {
         self.into()
     }
 }
+
+/// Range of versions, following Cargo's requirement syntax:
+/// `^1.2.3` (or just `1.2.3`), `~1.2.3` and `=1.2.3`
+/// A pre-release only matches a requirement on a pre-release of the same version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionReq {
+    /// Changes that don't modify the left-most non-zero part, e.g. `^1.2.3` is `>=1.2.3, <2.0.0`
+    /// and `^0.2.3` is `>=0.2.3, <0.3.0`
+    Caret(Version),
+    /// Only patch updates, e.g. `~1.2.3` is `>=1.2.3, <1.3.0`
+    Tilde(Version),
+    Exact(Version),
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &Version) -> bool {
+        let req = self.version();
+        if version.is_pre_release()
+            && !(req.is_pre_release() && req.to_release() == version.to_release())
+        {
+            return false;
+        }
+        match self {
+            VersionReq::Caret(req) => {
+                version >= req
+                    && match (req.major, req.minor) {
+                        (0, 0) => version == req,
+                        (0, _) => version.major == 0 && version.minor == req.minor,
+                        _ => req.same_major(version),
+                    }
+            }
+            VersionReq::Tilde(req) => {
+                version >= req && version.major == req.major && version.minor == req.minor
+            }
+            VersionReq::Exact(req) => version == req,
+        }
+    }
+
+    fn version(&self) -> &Version {
+        match self {
+            VersionReq::Caret(version)
+            | VersionReq::Tilde(version)
+            | VersionReq::Exact(version) => version,
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(version) = s.strip_prefix('^') {
+            version.trim_start().parse().map(VersionReq::Caret)
+        } else if let Some(version) = s.strip_prefix('~') {
+            version.trim_start().parse().map(VersionReq::Tilde)
+        } else if let Some(version) = s.strip_prefix('=') {
+            version.trim_start().parse().map(VersionReq::Exact)
+        } else {
+            s.parse().map(VersionReq::Caret)
+        }
+    }
+}
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (op, version) = match self {
+            VersionReq::Caret(version) => ('^', version),
+            VersionReq::Tilde(version) => ('~', version),
+            VersionReq::Exact(version) => ('=', version),
+        };
+        write!(
+            f,
+            "{op}{}.{}.{}",
+            version.major, version.minor, version.patch
+        )?;
+        if let Some(pre) = &version.pre {
+            write!(f, "-{pre}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_all_formats() {
+        let expected = Version::new(1, 2, 3);
+        assert_eq!(v("1.2.3"), expected);
+        assert_eq!(v("v1.2.3"), expected);
+        assert_eq!(v("v1_2_3"), expected);
+        assert_eq!(v("1_2_3"), expected);
+        assert!("1.2".parse::<Version>().is_err());
+        assert!("1.2.3.4".parse::<Version>().is_err());
+        assert!("1.2_3".parse::<Version>().is_err());
+        assert!("a.b.c".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let version = Version::new(4, 0, 12);
+        assert_eq!(version.to_string().parse::<Version>().unwrap(), version);
+    }
+
+    #[test]
+    fn orders_by_major_minor_patch() {
+        assert!(v("1.0.0") > v("0.9.9"));
+        assert!(v("1.2.0") > v("1.1.9"));
+        assert!(v("1.2.4") > v("1.2.3"));
+        assert_eq!(v("2.0.0").max(v("1.9.9")), v("2.0.0"));
+    }
+
+    #[test]
+    fn parses_pre_release_and_build() {
+        let version = v("v1_3_0-rc.1+abc123");
+        assert_eq!(version.pre(), Some("rc.1"));
+        assert_eq!(version.build(), Some("abc123"));
+        assert_eq!(version.to_release(), v("1.3.0"));
+        assert_eq!(version.to_string(), "v1_3_0-rc.1+abc123");
+        assert_eq!(version.to_key(), b"1_3_0-rc.1".to_vec());
+        assert!("1.3.0-".parse::<Version>().is_err());
+        assert!("1.3.0-rc..1".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn pre_releases_come_before_release() {
+        assert!(v("1.3.0-rc.1") < v("1.3.0"));
+        assert!(v("1.3.0-rc.1") > v("1.2.9"));
+        assert!(v("1.3.0-alpha") < v("1.3.0-alpha.1"));
+        assert!(v("1.3.0-alpha.1") < v("1.3.0-beta"));
+        assert!(v("1.3.0-rc.2") < v("1.3.0-rc.10"));
+    }
+
+    #[test]
+    fn borsh_layout_is_unchanged() {
+        let bytes = Version::new(1, 2, 3).try_to_vec().unwrap();
+        assert_eq!(bytes, vec![3, 0, 2, 0, 1, 0]);
+        assert_eq!(Version::try_from_slice(&bytes).unwrap(), v("1.2.3"));
+
+        let pre_release = v("1.3.0-rc.1+abc123");
+        let bytes = pre_release.try_to_vec().unwrap();
+        assert_eq!(Version::try_from_slice(&bytes).unwrap(), v("1.3.0"));
+    }
+
+    #[test]
+    fn caret_and_tilde_matching() {
+        let caret: VersionReq = "^1.2.3".parse().unwrap();
+        assert!(caret.matches(&v("1.2.3")));
+        assert!(caret.matches(&v("1.9.0")));
+        assert!(!caret.matches(&v("1.2.2")));
+        assert!(!caret.matches(&v("2.0.0")));
+
+        let zero_caret: VersionReq = "0.2.3".parse().unwrap();
+        assert!(zero_caret.matches(&v("0.2.9")));
+        assert!(!zero_caret.matches(&v("0.3.0")));
+
+        let tilde: VersionReq = "~1.2.3".parse().unwrap();
+        assert!(tilde.matches(&v("1.2.9")));
+        assert!(!tilde.matches(&v("1.3.0")));
+
+        let exact: VersionReq = "=v1_2_3".parse().unwrap();
+        assert!(exact.matches(&v("1.2.3")));
+        assert!(!exact.matches(&v("1.2.4")));
+
+        assert!(!caret.matches(&v("1.3.0-rc.1")));
+        let pre_caret: VersionReq = "^1.3.0-rc.1".parse().unwrap();
+        assert!(pre_caret.matches(&v("1.3.0-rc.2")));
+        assert!(pre_caret.matches(&v("1.3.1")));
+        assert!(!pre_caret.matches(&v("1.4.0-rc.1")));
+    }
+}
@@ -0,0 +1,179 @@
+//! Checks that a wasm binary can still be redeployed once deployed,
+//! i.e. that it exports the methods `near-riffs-core` uses to redeploy.
+//! Used by the registry when publishing and usable off-chain before publishing.
+use std::fmt::Display;
+
+/// `\0asm` followed by version 1
+const MAGIC_HEADER: &[u8] = b"\0asm\x01\0\0\0";
+const EXPORT_SECTION: u8 = 7;
+const FUNCTION_EXPORT: u8 = 0;
+
+/// Exports needed to claim the contract, to redeploy it again and to record its deployments
+pub const REQUIRED_EXPORTS: [&str; 5] = [
+    "set_owner",
+    "redeploy",
+    "on_redeploy",
+    "on_deployed",
+    "seed_deployment",
+];
+
+/// Also needed by contracts built with `near-riffs-core`'s `dao_redeploy` feature. A registry
+/// can't tell such a build apart, so it only checks `REQUIRED_EXPORTS` and DAO builds should
+/// be checked off-chain with `validate_exports`.
+pub const DAO_REQUIRED_EXPORTS: [&str; 1] = ["on_redeploy_approved"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasmError {
+    /// Doesn't start with the wasm magic header
+    NotWasm,
+    Malformed,
+    /// Required exports that are missing
+    MissingExports(Vec<&'static str>),
+}
+
+impl Display for WasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasmError::NotWasm => write!(f, "Not a wasm binary: missing magic header"),
+            WasmError::Malformed => write!(f, "Malformed wasm binary"),
+            WasmError::MissingExports(missing) => write!(
+                f,
+                "Wasm can't be redeployed, it doesn't export {}",
+                missing.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WasmError {}
+
+/// Check the magic header and that all of `REQUIRED_EXPORTS` are exported
+pub fn validate(wasm: &[u8]) -> Result<(), WasmError> {
+    validate_exports(wasm, &REQUIRED_EXPORTS)
+}
+
+/// Check the magic header and that all of `required` are exported, e.g. `REQUIRED_EXPORTS`
+/// together with `DAO_REQUIRED_EXPORTS` for a DAO build
+pub fn validate_exports(wasm: &[u8], required: &[&'static str]) -> Result<(), WasmError> {
+    let exports = exported_functions(wasm)?;
+    let missing: Vec<_> = required
+        .iter()
+        .copied()
+        .filter(|required| !exports.contains(required))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(WasmError::MissingExports(missing))
+    }
+}
+
+/// Names of the functions exported by a wasm binary
+pub fn exported_functions(wasm: &[u8]) -> Result<Vec<&str>, WasmError> {
+    let mut bytes = wasm.strip_prefix(MAGIC_HEADER).ok_or(WasmError::NotWasm)?;
+    let mut exports = vec![];
+    while !bytes.is_empty() {
+        let id = take(&mut bytes, 1)?[0];
+        let len = read_u32(&mut bytes)? as usize;
+        let mut section = take(&mut bytes, len)?;
+        if id != EXPORT_SECTION {
+            continue;
+        }
+        for _ in 0..read_u32(&mut section)? {
+            let name_len = read_u32(&mut section)? as usize;
+            let name = std::str::from_utf8(take(&mut section, name_len)?)
+                .map_err(|_| WasmError::Malformed)?;
+            let kind = take(&mut section, 1)?[0];
+            read_u32(&mut section)?;
+            if kind == FUNCTION_EXPORT {
+                exports.push(name);
+            }
+        }
+    }
+    Ok(exports)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], WasmError> {
+    if bytes.len() < len {
+        return Err(WasmError::Malformed);
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Reads an unsigned LEB128 encoded integer
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, WasmError> {
+    let mut result = 0;
+    for shift in (0..32).step_by(7) {
+        let byte = take(bytes, 1)?[0];
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(WasmError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module with only an export section, exporting functions with the given names
+    fn module(exports: &[&str]) -> Vec<u8> {
+        let mut section = vec![exports.len() as u8];
+        for (index, name) in exports.iter().enumerate() {
+            section.push(name.len() as u8);
+            section.extend(name.as_bytes());
+            section.extend([FUNCTION_EXPORT, index as u8]);
+        }
+        let mut wasm = MAGIC_HEADER.to_vec();
+        // A custom section, which is skipped
+        wasm.extend([0, 3, 1, b'a', 0]);
+        wasm.extend([EXPORT_SECTION, section.len() as u8]);
+        wasm.extend(section);
+        wasm
+    }
+
+    #[test]
+    fn accepts_redeployable_wasm() {
+        let mut exports = REQUIRED_EXPORTS.to_vec();
+        exports.push("fetch");
+        assert_eq!(validate(&module(&exports)), Ok(()));
+    }
+
+    #[test]
+    fn checks_dao_exports_on_request() {
+        let wasm = module(&REQUIRED_EXPORTS);
+        let dao_exports = [REQUIRED_EXPORTS.as_slice(), &DAO_REQUIRED_EXPORTS].concat();
+        assert_eq!(
+            validate_exports(&wasm, &dao_exports),
+            Err(WasmError::MissingExports(vec!["on_redeploy_approved"]))
+        );
+        assert_eq!(
+            validate_exports(&module(&dao_exports), &dao_exports),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn reports_missing_exports() {
+        let wasm = module(&["set_owner", "on_deployed", "fetch"]);
+        assert_eq!(
+            validate(&wasm),
+            Err(WasmError::MissingExports(vec![
+                "redeploy",
+                "on_redeploy",
+                "seed_deployment"
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejects_other_bytes() {
+        assert_eq!(validate(b"not wasm"), Err(WasmError::NotWasm));
+        let mut truncated = module(&REQUIRED_EXPORTS);
+        truncated.pop();
+        assert_eq!(validate(&truncated), Err(WasmError::Malformed));
+    }
+}
@@ -3,7 +3,9 @@ use near_units::parse_near as near;
 use serde_json::json;
 use workspaces::Contract;
 
-use crate::utils::{AccountIdTools, AssertResult, Contracts, IntoVec, TestEnv, ALICE};
+use crate::utils::{
+    AccountIdTools, AssertResult, Contracts, IntoVec, TestEnv, ALICE, BOB, FACTORY,
+};
 
 #[tokio::test]
 async fn initialize_correctly() -> anyhow::Result<()> {
@@ -17,11 +19,25 @@ async fn initialize_correctly() -> anyhow::Result<()> {
 #[tokio::test]
 async fn owner_can_transfer() -> anyhow::Result<()> {
     let (bootloader, test_env) = TestEnv::with_bootloader().await?;
+    let alice = test_env.alice().await?;
 
     test_env
         .root
         .call(bootloader.id(), "set_owner")
-        .args(ALICE.to_vec())
+        .args(alice.to_vec())
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    let owner = bootloader
+        .view("get_owner_json", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(owner, test_env.root.id().to_string());
+
+    alice
+        .call(bootloader.id(), "accept_owner")
+        .deposit(1)
         .transact()
         .await?
         .assert_success();
@@ -30,7 +46,43 @@ async fn owner_can_transfer() -> anyhow::Result<()> {
         .await?
         .json::<String>()?;
 
-    assert_eq!(owner, ALICE.to_string());
+    assert_eq!(owner, alice.id().to_string());
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_pending_owner_can_accept() -> anyhow::Result<()> {
+    let (bootloader, test_env) = TestEnv::with_bootloader().await?;
+    let alice = test_env.alice().await?;
+    let bob = test_env.create_subaccount(BOB).await?;
+
+    test_env
+        .root
+        .call(bootloader.id(), "propose_owner")
+        .args(alice.to_vec())
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    bob.call(bootloader.id(), "accept_owner")
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
+
+    test_env
+        .root
+        .call(bootloader.id(), "cancel_owner_proposal")
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    alice
+        .call(bootloader.id(), "accept_owner")
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
     Ok(())
 }
 
@@ -47,6 +99,39 @@ async fn non_owner_cannot_transfer() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn renounced_contract_is_frozen() -> anyhow::Result<()> {
+    let (bootloader, test_env) = TestEnv::with_bootloader().await?;
+    let alice = test_env.alice().await?;
+
+    test_env
+        .root
+        .call(bootloader.id(), "renounce_ownership")
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    assert!(bootloader.view("is_frozen", vec![]).await?.json::<bool>()?);
+    let owner = bootloader
+        .view("get_owner_json", vec![])
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(owner, None);
+
+    alice
+        .call(bootloader.id(), "set_owner")
+        .args(alice.to_vec())
+        .transact()
+        .await?
+        .assert_failure();
+    let registry = test_env.registry(Contracts::Bootloader).await?;
+    test_env
+        .redeploy(&test_env.root, &bootloader, &registry)
+        .await?
+        .assert_failure();
+    Ok(())
+}
+
 #[tokio::test]
 async fn can_create_registry() -> anyhow::Result<()> {
     let testenv = TestEnv::init().await?;
@@ -137,6 +222,498 @@ async fn can_upgrade_from_factory() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn cannot_redeploy_yanked_version() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    let bootloader = &testenv.bootloader().await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    root.call(factory.id(), "yank")
+        .args_json(json!({ "version": "0_0_2" }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    root.call(alice.id(), "redeploy")
+        .args(format!("v0_0_2.{}", factory.id()).into_bytes())
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    assert_equal_contracts(alice, bootloader).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn redeploy_checks_code_hash() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    let bootloader_hash = alice.view_account().await?.code_hash;
+    testenv
+        .redeploy_with_code_hash(root, alice, factory, &bootloader_hash)
+        .await?
+        .assert_failure();
+
+    let factory_hash = factory.view_account().await?.code_hash;
+    testenv
+        .redeploy_with_code_hash(root, alice, factory, &factory_hash)
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn redeploy_with_explicit_gas_budgets() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    // Not enough gas to fetch the code
+    root.call(alice.id(), "redeploy")
+        .args_json(json!({
+            "registry": factory.id(),
+            "fetch_gas": 1_000_000_000u64,
+        }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+
+    // Budgets can be numbers or strings
+    root.call(alice.id(), "redeploy")
+        .args_json(json!({
+            "registry": factory.id(),
+            "fetch_gas": 50_000_000_000_000u64,
+            "deploy_gas": "100000000000000",
+        }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn redeploy_only_from_trusted_registry() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    root.call(alice.id(), "add_trusted_registry")
+        .args_json(json!({ "account_id": root.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_failure();
+
+    root.call(alice.id(), "add_trusted_registry")
+        .args_json(json!({ "account_id": factory.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn redeploy_only_approved_code_hash() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    let bootloader_hash = alice.view_account().await?.code_hash.to_string();
+    root.call(alice.id(), "add_approved_code_hash")
+        .args_json(json!({ "code_hash": bootloader_hash }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_failure();
+
+    // Emptying the list would approve any code again
+    root.call(alice.id(), "remove_approved_code_hash")
+        .args_json(json!({ "code_hash": bootloader_hash }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
+
+    let factory_hash = factory.view_account().await?.code_hash.to_string();
+    root.call(alice.id(), "add_approved_code_hash")
+        .args_json(json!({ "code_hash": factory_hash }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    root.call(alice.id(), "remove_approved_code_hash")
+        .args_json(json!({ "code_hash": bootloader_hash }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn redeploy_requires_trusted_release_signature() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    let release_key = near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, "release");
+    let public_key = release_key.public_key().to_string();
+
+    root.call(alice.id(), "add_trusted_release_key")
+        .args_json(json!({ "public_key": public_key }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_failure();
+
+    root.call(factory.id(), "add_release_key")
+        .args_json(json!({ "public_key": public_key }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    let signature = match release_key.sign(near_primitives::hash::hash(&FACTORY).as_ref()) {
+        near_crypto::Signature::ED25519(signature) => signature.to_bytes(),
+        _ => unreachable!("release key is ed25519"),
+    };
+    root.call(factory.id(), "sign_release")
+        .args_json(json!({
+            "version": "0_0_2",
+            "signature": {
+                "public_key": public_key,
+                "signature": base64::encode(signature),
+            },
+        }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_migration_reverts_redeploy() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    let bootloader = &testenv.bootloader().await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    let version = factory
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    testenv
+        .root
+        .call(alice.id(), "redeploy")
+        .args_json(json!({
+            "account_id": format!("{}.{}", version, factory.id()),
+            "migrate": "no_such_method",
+        }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    assert_equal_contracts(alice, bootloader).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_rollback_to_previous_deployment() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    let bootloader = &testenv.bootloader().await?;
+    let deployed = alice
+        .view("deployed_version", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(deployed["version"], "0_0_1");
+
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+
+    let deployed = alice
+        .view("deployed_version", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(deployed["version"], "0_0_2");
+    assert_eq!(deployed["registry"], factory.id().to_string());
+    assert_eq!(
+        deployed["code_hash"],
+        factory.view_account().await?.code_hash.to_string()
+    );
+
+    root.call(alice.id(), "rollback")
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, bootloader).await;
+    let deployed = alice
+        .view("deployed_version", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(deployed["version"], "0_0_1");
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_rollback_after_channel_redeploy() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    let bootloader = &testenv.bootloader().await?;
+    let beta = format!("beta.{}", factory.id());
+
+    for (contract, version) in [
+        (Contracts::Factory, "0_0_2"),
+        (Contracts::Bootloader, "0_0_3"),
+    ] {
+        testenv
+            .patch(factory.id(), contract.into())
+            .await?
+            .assert_success();
+        root.call(factory.id(), "set_channel")
+            .args_json(json!({ "channel": "beta", "version": version }))
+            .deposit(1)
+            .transact()
+            .await?
+            .assert_success();
+        root.call(alice.id(), "redeploy")
+            .args_json(beta.clone())
+            .deposit(1)
+            .max_gas()
+            .transact()
+            .await?
+            .assert_success();
+        let deployed = alice
+            .view("deployed_version", vec![])
+            .await?
+            .json::<serde_json::Value>()?;
+        assert_eq!(deployed["version"], version);
+    }
+    assert_equal_contracts(alice, bootloader).await;
+
+    // Returns to 0_0_2, not to what beta points at now
+    root.call(alice.id(), "rollback")
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    let deployed = alice
+        .view("deployed_version", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(deployed["version"], "0_0_2");
+    Ok(())
+}
+
+#[tokio::test]
+async fn timelocked_redeploy_is_scheduled() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv
+        .factory("factory", Contracts::BootloaderTimelock)
+        .await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    testenv
+        .redeploy(root, alice, factory)
+        .await?
+        .assert_failure();
+    let code_hash = factory.view_account().await?.code_hash;
+    testenv
+        .redeploy_with_code_hash(root, alice, factory, &code_hash)
+        .await?
+        .assert_success();
+    assert_ne!(alice.view_account().await?.code_hash, code_hash);
+    let pending = alice
+        .view("pending_redeploy", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(pending["version"], "0_0_2");
+
+    root.call(alice.id(), "execute_redeploy")
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    root.call(alice.id(), "cancel_redeploy")
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    let pending = alice
+        .view("pending_redeploy", vec![])
+        .await?
+        .json::<Option<serde_json::Value>>()?;
+    assert!(pending.is_none());
+
+    // A typo can't lock upgrades forever
+    root.call(alice.id(), "set_redeploy_delay")
+        .args_json(json!({ "delay": u64::MAX.to_string() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
+    testenv
+        .redeploy_with_code_hash(root, alice, factory, &code_hash)
+        .await?
+        .assert_success();
+    Ok(())
+}
+
+#[tokio::test]
+async fn timelocked_redeploy_executes_after_delay() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv
+        .factory("factory", Contracts::BootloaderTimelock)
+        .await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    let code_hash = factory.view_account().await?.code_hash;
+    testenv
+        .redeploy_with_code_hash(root, alice, factory, &code_hash)
+        .await?
+        .assert_success();
+
+    let pending = alice
+        .view("pending_redeploy", vec![])
+        .await?
+        .json::<serde_json::Value>()?;
+    let earliest_timestamp: u64 = pending["earliest_timestamp"]
+        .as_str()
+        .expect("earliest_timestamp is a string")
+        .parse()?;
+    while testenv.worker.view_block().await?.timestamp() < earliest_timestamp {
+        testenv.worker.fast_forward(10_000).await?;
+    }
+    root.call(alice.id(), "execute_redeploy")
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_redeploy_current_version_of_registry() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    let alice = &testenv.create_subaccount_and_deploy(factory, ALICE).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    testenv
+        .root
+        .call(alice.id(), "redeploy")
+        .args_json(json!({ "registry": factory.id() }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_equal_contracts(alice, factory).await;
+    Ok(())
+}
+
 #[tokio::test]
 async fn can_create_factory_of_factories() -> anyhow::Result<()> {
     let testenv = &TestEnv::init().await?;
@@ -0,0 +1,115 @@
+use near_units::parse_near as near;
+use serde_json::json;
+use workspaces::{operations::Function, Contract};
+
+use crate::utils::{AccountIdTools, AssertResult, Contracts, TestEnv, BOB};
+
+#[tokio::test]
+async fn redeploy_requires_dao_approval() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let factory = &testenv.factory("factory", Contracts::Bootloader).await?;
+    testenv
+        .patch(factory.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    let dao = testenv
+        .worker
+        .dev_deploy(&Vec::<u8>::from(Contracts::DaoStandIn))
+        .await?;
+
+    // Deploy a bootloader owned by the DAO
+    let contract_id = root.id().subaccount("governed");
+    root.batch(&contract_id)
+        .create_account()
+        .transfer(near!("6 N"))
+        .deploy(&Vec::<u8>::from(Contracts::BootloaderDao))
+        .call(Function::new("set_owner").args(dao.id().as_bytes().to_vec()))
+        .transact()
+        .await?
+        .assert_success();
+    let contract =
+        Contract::from_secret_key(contract_id.clone(), contract_id.to_sk(), &testenv.worker);
+
+    let code_hash = factory.view_account().await?.code_hash.to_string();
+    let redeploy_args = json!({
+        "account_id": format!("v0_0_2.{}", factory.id()),
+        "code_hash": code_hash,
+    });
+    root.call(contract.id(), "redeploy")
+        .args_json(redeploy_args.clone())
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+
+    root.call(dao.id(), "approve")
+        .args_json(json!({
+            "approval": {
+                "contract_id": contract.id(),
+                "registry": factory.id(),
+                "version": "0_0_2",
+                "code_hash": code_hash,
+                "migrate": null,
+                "fetch_gas": null,
+                "deploy_gas": null,
+            }
+        }))
+        .transact()
+        .await?
+        .assert_success();
+
+    // The approval doesn't cover a migration added by the caller
+    root.call(contract.id(), "redeploy")
+        .args_json(json!({
+            "account_id": format!("v0_0_2.{}", factory.id()),
+            "code_hash": code_hash,
+            "migrate": "set_owner",
+            "migrate_args": base64::encode(format!("\"{}\"", root.id())),
+        }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    assert_ne!(
+        contract.view_account().await?.code_hash,
+        factory.view_account().await?.code_hash
+    );
+
+    // Griefing calls can't use up the approval without deploying
+    let griefer = testenv.create_subaccount(BOB).await?;
+    griefer
+        .call(contract.id(), "redeploy")
+        .args_json(json!({
+            "account_id": format!("v0_0_2.{}", factory.id()),
+            "code_hash": code_hash,
+            "deploy_gas": "1",
+        }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    griefer
+        .call(contract.id(), "redeploy")
+        .args_json(redeploy_args.clone())
+        .gas(50_000_000_000_000)
+        .transact()
+        .await?
+        .assert_failure();
+    assert_ne!(
+        contract.view_account().await?.code_hash,
+        factory.view_account().await?.code_hash
+    );
+
+    root.call(contract.id(), "redeploy")
+        .args_json(redeploy_args)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_eq!(
+        contract.view_account().await?.code_hash,
+        factory.view_account().await?.code_hash
+    );
+    Ok(())
+}
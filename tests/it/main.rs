@@ -1,2 +1,5 @@
 mod bootloader;
+mod dao;
+mod multisig;
+mod registry;
 pub(crate) mod utils;
@@ -0,0 +1,88 @@
+use near_units::parse_near as near;
+use serde_json::json;
+
+use crate::utils::{AccountIdTools, AssertResult, Contracts, TestEnv, BOB};
+
+#[tokio::test]
+async fn confirmed_action_is_executed() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let contract = &testenv
+        .deploy_and_init_subaccount(
+            &Vec::<u8>::from(Contracts::BootloaderMultisig),
+            &root.id().subaccount("multisig"),
+            root,
+        )
+        .await?;
+    let alice = &testenv.alice().await?;
+    let bob = &testenv.create_subaccount(BOB).await?;
+
+    root.call(contract.id(), "set_multisig")
+        .args_json(json!({ "members": [alice.id(), bob.id()], "threshold": 2 }))
+        .transact()
+        .await?
+        .assert_success();
+
+    // Hand ownership to the contract itself
+    root.call(contract.id(), "propose_owner")
+        .args(contract.id().as_bytes().to_vec())
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    let accept_owner = json!({ "action": { "FunctionCall": {
+        "method_name": "accept_owner",
+        "args": "",
+        "deposit": "1",
+    }}});
+    alice
+        .call(contract.id(), "propose_action")
+        .args_json(accept_owner)
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    bob.call(contract.id(), "confirm_action")
+        .args_json(json!({ "id": "0" }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let owner = contract
+        .view("get_owner_json", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(owner, contract.id().to_string());
+
+    let set_owner = json!({ "action": { "SetOwner": { "account_id": alice.id() }}});
+    alice
+        .call(contract.id(), "propose_action")
+        .args_json(set_owner)
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    let pending = contract
+        .view("get_pending_owner", vec![])
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(pending, None);
+    root.call(contract.id(), "confirm_action")
+        .args_json(json!({ "id": "1" }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    bob.call(contract.id(), "confirm_action")
+        .args_json(json!({ "id": "1" }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let pending = contract
+        .view("get_pending_owner", vec![])
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(pending, Some(alice.id().to_string()));
+    Ok(())
+}
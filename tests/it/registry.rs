@@ -0,0 +1,885 @@
+use near_units::parse_near as near;
+use serde_json::json;
+
+use crate::utils::{
+    data_url, AccountIdTools, AssertResult, Contracts, TestEnv, BOB, BOOTLOADER, DAO_STAND_IN,
+    FACTORY, REGISTRY,
+};
+
+#[tokio::test]
+async fn pre_release_is_only_current_once_promoted() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .root
+        .call(registry.id(), "pre_release")
+        .args_json(json!({
+            "version": "0.1.0-rc.1",
+            "contract_bytes": data_url(&FACTORY),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_1");
+    let fetched = registry.view("fetch", b"0.1.0-rc.1".to_vec()).await?.result;
+    assert_eq!(fetched, FACTORY.to_vec());
+
+    testenv
+        .root
+        .call(registry.id(), "promote")
+        .args_json(json!({ "version": "0.1.0-rc.1" }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_1_0");
+    let fetched = registry.view("fetch", vec![]).await?.result;
+    assert_eq!(fetched, FACTORY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_follows_channel() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(registry.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    for (channel, version) in [("stable", "0_0_1"), ("beta", "0_0_2")] {
+        testenv
+            .root
+            .call(registry.id(), "set_channel")
+            .args_json(json!({ "channel": channel, "version": version }))
+            .deposit(1)
+            .transact()
+            .await?
+            .assert_success();
+    }
+
+    let stable = registry.view("fetch", b"stable".to_vec()).await?.result;
+    assert_eq!(stable, BOOTLOADER.to_vec());
+    let beta = registry.view("fetch", b"beta".to_vec()).await?.result;
+    assert_eq!(beta, FACTORY.to_vec());
+    registry
+        .view("fetch", b"nightly".to_vec())
+        .await
+        .expect_err("nightly channel doesn't exist");
+    Ok(())
+}
+
+#[tokio::test]
+async fn release_history_includes_metadata() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    let metadata = json!({
+        "changelog": "Initial release",
+        "repository": "https://github.com/ahalabs/near-riffs",
+        "commit": "abc123",
+        "build_command": "npm run build",
+    });
+    testenv
+        .root
+        .call(registry.id(), "set_release_metadata")
+        .args_json(json!({ "version": "0.0.1", "metadata": metadata }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    testenv
+        .patch(registry.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+
+    let history = registry
+        .view(
+            "release_history",
+            json!({ "limit": "1" }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(
+        history,
+        json!([{ "version": "v0_0_1", "metadata": metadata, "status": null }])
+    );
+    let history = registry
+        .view(
+            "release_history",
+            json!({ "from_index": "1" }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(
+        history,
+        json!([{ "version": "v0_0_2", "metadata": null, "status": null }])
+    );
+    let history = registry
+        .view("release_history", vec![])
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+    assert_eq!(history.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn versions_are_listed_newest_first_by_major() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(registry.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    root.call(registry.id(), "major")
+        .args(BOOTLOADER.to_vec())
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+
+    let versions = registry
+        .view(
+            "versions",
+            json!({ "from_index": "1", "limit": "1" })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .json::<Vec<String>>()?;
+    assert_eq!(versions, vec!["v0_0_2"]);
+    let versions = registry
+        .view("versions", json!({ "major": 0 }).to_string().into_bytes())
+        .await?
+        .json::<Vec<String>>()?;
+    assert_eq!(versions, vec!["v0_0_2", "v0_0_1"]);
+    let latest = registry
+        .view(
+            "latest_for_major",
+            json!({ "major": 0 }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(latest.as_deref(), Some("v0_0_2"));
+    let latest = registry
+        .view(
+            "latest_for_major",
+            json!({ "major": 2 }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<Option<String>>()?;
+    assert_eq!(latest, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn yanked_version_is_only_fetched_when_forced() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(registry.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    testenv
+        .root
+        .call(registry.id(), "yank")
+        .args_json(json!({ "version": "0_0_2" }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_1");
+    let fetched = registry.view("fetch", vec![]).await?.result;
+    assert_eq!(fetched, BOOTLOADER.to_vec());
+    registry
+        .view("fetch", b"0_0_2".to_vec())
+        .await
+        .expect_err("yanked version is refused");
+    let forced = registry
+        .view(
+            "fetch",
+            json!({ "version": "0_0_2", "force": true })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .result;
+    assert_eq!(forced, FACTORY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn pruned_version_is_kept_but_not_fetched() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(registry.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    root.call(registry.id(), "prune")
+        .args_json(json!({ "versions": ["0_0_2"] }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
+
+    let balance = root.view_account().await?.balance;
+    root.call(registry.id(), "prune")
+        .args_json(json!({ "older_than": "0_0_2" }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert!(root.view_account().await?.balance > balance);
+
+    let versions = registry
+        .view("versions", vec![])
+        .await?
+        .json::<Vec<String>>()?;
+    assert_eq!(versions, vec!["v0_0_2", "v0_0_1"]);
+    let status = registry
+        .view(
+            "release_status",
+            json!({ "version": "0_0_1" }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<String>()?;
+    assert_eq!(status, "pruned");
+    let err = registry
+        .view("fetch", b"0_0_1".to_vec())
+        .await
+        .expect_err("pruned version is refused");
+    assert!(err.to_string().contains("pruned"));
+    let fetched = registry.view("fetch", vec![]).await?.result;
+    assert_eq!(fetched, FACTORY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn served_versions_are_not_pruned() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    for contract in [Contracts::Factory, Contracts::Registry] {
+        testenv
+            .patch(registry.id(), contract.into())
+            .await?
+            .assert_success();
+    }
+    root.call(registry.id(), "yank")
+        .args_json(json!({ "version": "0_0_3" }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    // With the head yanked, 0_0_2 is what fetch serves
+    root.call(registry.id(), "prune")
+        .args_json(json!({ "versions": ["0_0_2"] }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    root.call(registry.id(), "set_channel")
+        .args_json(json!({ "channel": "stable", "version": "0_0_1" }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    root.call(registry.id(), "prune")
+        .args_json(json!({ "versions": ["0_0_1"] }))
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    let fetched = registry.view("fetch", b"stable".to_vec()).await?.result;
+    assert_eq!(fetched, BOOTLOADER.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_publish_in_chunks() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    // Publish the registry's own code, whose hash the sandbox reports
+    let code_hash = registry.view_account().await?.code_hash;
+    root.call(registry.id(), "start_upload")
+        .transact()
+        .await?
+        .assert_success();
+    let (first, second) = REGISTRY.split_at(REGISTRY.len() / 2);
+    for (offset, chunk) in [(0, first), (first.len(), second)] {
+        root.call(registry.id(), "append_chunk")
+            .args_json(json!({
+                "offset": offset.to_string(),
+                "chunk": base64::encode(chunk),
+            }))
+            .deposit(near!("5 N"))
+            .max_gas()
+            .transact()
+            .await?
+            .assert_success();
+    }
+    root.call(registry.id(), "append_chunk")
+        .args_json(json!({ "offset": "0", "chunk": base64::encode(first) }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+
+    // The chunks already paid for the binary's storage
+    root.call(registry.id(), "finalize_upload")
+        .args_json(json!({ "code_hash": code_hash.to_string(), "kind": "minor" }))
+        .deposit(near!("0.1 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_1_0");
+    let fetched = registry.view("fetch", vec![]).await?.result;
+    assert_eq!(fetched, REGISTRY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn finalizing_a_stored_binary_refunds_chunks() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Registry).await?;
+    let code_hash = registry.view_account().await?.code_hash;
+    root.call(registry.id(), "start_upload")
+        .transact()
+        .await?
+        .assert_success();
+    let (first, second) = REGISTRY.split_at(REGISTRY.len() / 2);
+    for (offset, chunk) in [(0, first), (first.len(), second)] {
+        root.call(registry.id(), "append_chunk")
+            .args_json(json!({
+                "offset": offset.to_string(),
+                "chunk": base64::encode(chunk),
+            }))
+            .deposit(near!("5 N"))
+            .max_gas()
+            .transact()
+            .await?
+            .assert_success();
+    }
+
+    // The binary is already stored as 0_0_1, so the chunks' stake goes back to the uploader
+    let balance = root.view_account().await?.balance;
+    root.call(registry.id(), "finalize_upload")
+        .args_json(json!({ "code_hash": code_hash.to_string(), "kind": "patch" }))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let refund = root.view_account().await?.balance - balance;
+    assert!(
+        refund >= REGISTRY.len() as u128 * 10u128.pow(19) / 2,
+        "Uploader was only refunded {refund}"
+    );
+    let fetched = registry.view("fetch", b"0_0_2".to_vec()).await?.result;
+    assert_eq!(fetched, REGISTRY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn aborted_upload_refunds_uploader() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let alice = &testenv.alice().await?;
+    let registry = &testenv
+        .deploy_and_init_subaccount(
+            &Vec::from(Contracts::RegistryAdmins),
+            &root.id().subaccount("registry"),
+            root,
+        )
+        .await?;
+    root.call(registry.id(), "add_admin")
+        .args_json(json!({ "account_id": alice.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    alice
+        .call(registry.id(), "start_upload")
+        .transact()
+        .await?
+        .assert_success();
+    alice
+        .call(registry.id(), "append_chunk")
+        .args_json(json!({ "offset": "0", "chunk": base64::encode(&*BOOTLOADER) }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    root.call(registry.id(), "append_chunk")
+        .args_json(json!({
+            "offset": BOOTLOADER.len().to_string(),
+            "chunk": base64::encode(&*BOOTLOADER),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+
+    let balance = alice.view_account().await?.balance;
+    root.call(registry.id(), "abort_upload")
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let refund = alice.view_account().await?.balance - balance;
+    assert!(
+        refund >= BOOTLOADER.len() as u128 * 10u128.pow(19),
+        "Uploader was only refunded {refund}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn identical_binaries_are_stored_once() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    let storage_usage = registry.view_account().await?.storage_usage;
+    testenv
+        .patch(registry.id(), Contracts::Bootloader.into())
+        .await?
+        .assert_success();
+    let added = registry.view_account().await?.storage_usage - storage_usage;
+    assert!(
+        added < 1_000,
+        "Republishing stored {added} bytes instead of pointing at the existing binary"
+    );
+
+    let code_hash = registry
+        .view(
+            "version_code_hash",
+            json!({ "version": "0_0_2" }).to_string().into_bytes(),
+        )
+        .await?
+        .json::<String>()?;
+    let fetched = registry
+        .view(
+            "fetch_by_hash",
+            json!({ "code_hash": code_hash }).to_string().into_bytes(),
+        )
+        .await?
+        .result;
+    assert_eq!(fetched, BOOTLOADER.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_redeploy_package_version() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    let bootloader = &testenv.bootloader().await?;
+    root.call(registry.id(), "create_package")
+        .args_json(json!({ "package": "registry", "owners": [root.id()] }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    root.call(registry.id(), "publish_package")
+        .args_json(json!({
+            "package": "registry",
+            "kind": "minor",
+            "contract_bytes": data_url(&REGISTRY),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let fetched = registry
+        .view("fetch", b"registry@0_1_0".to_vec())
+        .await?
+        .result;
+    assert_eq!(fetched, REGISTRY.to_vec());
+
+    root.call(bootloader.id(), "redeploy")
+        .args(format!("registry@v0_1_0.{}", registry.id()).into_bytes())
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_eq!(
+        bootloader.view_account().await?.code_hash,
+        registry.view_account().await?.code_hash
+    );
+
+    // Package versions are yanked and described like the registry's own versions
+    root.call(registry.id(), "set_release_metadata")
+        .args_json(json!({
+            "version": "registry@0_1_0",
+            "metadata": { "changelog": "Registry as a package" },
+        }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    let metadata = registry
+        .view(
+            "release_metadata",
+            json!({ "version": "registry@0_1_0" })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(metadata["changelog"], "Registry as a package");
+    root.call(registry.id(), "yank")
+        .args_json(json!({ "version": "registry@0_1_0" }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    registry
+        .view("fetch", b"registry@0_1_0".to_vec())
+        .await
+        .expect_err("yanked package version is refused");
+    let forced = registry
+        .view(
+            "fetch",
+            json!({ "version": "registry@0_1_0", "force": true })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .result;
+    assert_eq!(forced, REGISTRY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_redeploy_signed_package_version() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    let bootloader = &testenv.bootloader().await?;
+    let release_key = near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, "release");
+    let public_key = release_key.public_key().to_string();
+    let sign = |bytes: &[u8]| match release_key.sign(near_primitives::hash::hash(bytes).as_ref()) {
+        near_crypto::Signature::ED25519(signature) => json!({
+            "public_key": public_key,
+            "signature": base64::encode(signature.to_bytes()),
+        }),
+        _ => unreachable!("release key is ed25519"),
+    };
+    for (contract, method) in [
+        (registry, "add_release_key"),
+        (bootloader, "add_trusted_release_key"),
+    ] {
+        root.call(contract.id(), method)
+            .args_json(json!({ "public_key": public_key }))
+            .deposit(1)
+            .transact()
+            .await?
+            .assert_success();
+    }
+    root.call(registry.id(), "create_package")
+        .args_json(json!({ "package": "factory", "owners": [root.id()] }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+
+    // Signed after publishing
+    root.call(registry.id(), "publish_package")
+        .args_json(json!({
+            "package": "factory",
+            "kind": "minor",
+            "contract_bytes": data_url(&FACTORY),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    root.call(bootloader.id(), "redeploy")
+        .args(format!("factory@v0_1_0.{}", registry.id()).into_bytes())
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    root.call(registry.id(), "sign_release")
+        .args_json(json!({ "version": "factory@0_1_0", "signature": sign(&FACTORY) }))
+        .deposit(near!("1 N"))
+        .transact()
+        .await?
+        .assert_success();
+    let signature = registry
+        .view(
+            "release_signature",
+            json!({ "version": "factory@0_1_0" })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .json::<serde_json::Value>()?;
+    assert_eq!(signature["public_key"], public_key);
+    root.call(bootloader.id(), "redeploy")
+        .args(format!("factory@v0_1_0.{}", registry.id()).into_bytes())
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_eq!(
+        bootloader.view_account().await?.code_hash.to_string(),
+        near_primitives::hash::hash(&FACTORY).to_string()
+    );
+
+    // Signed when publishing
+    root.call(registry.id(), "publish_package")
+        .args_json(json!({
+            "package": "factory",
+            "kind": "patch",
+            "contract_bytes": data_url(&REGISTRY),
+            "signature": sign(&REGISTRY),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    root.call(bootloader.id(), "redeploy")
+        .args(format!("factory@v0_1_1.{}", registry.id()).into_bytes())
+        .deposit(1)
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    assert_eq!(
+        bootloader.view_account().await?.code_hash,
+        registry.view_account().await?.code_hash
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn admins_can_only_publish_patches() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let alice = &testenv.alice().await?;
+    let registry = &testenv
+        .deploy_and_init_subaccount(
+            &Vec::from(Contracts::RegistryAdmins),
+            &root.id().subaccount("registry"),
+            root,
+        )
+        .await?;
+    alice
+        .call(registry.id(), "patch")
+        .args(BOOTLOADER.to_vec())
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    root.call(registry.id(), "add_admin")
+        .args_json(json!({ "account_id": alice.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    alice
+        .call(registry.id(), "patch")
+        .args(BOOTLOADER.to_vec())
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    alice
+        .call(registry.id(), "major")
+        .args(FACTORY.to_vec())
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_1");
+
+    // Admins can't add admins, and revoked admins can't publish
+    let bob = &testenv.create_subaccount(BOB).await?;
+    alice
+        .call(registry.id(), "add_admin")
+        .args_json(json!({ "account_id": bob.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_failure();
+    root.call(registry.id(), "remove_admin")
+        .args_json(json!({ "account_id": alice.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+    alice
+        .call(registry.id(), "patch")
+        .args(FACTORY.to_vec())
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_1");
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejects_wasm_that_cannot_be_redeployed() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let registry = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(registry.id(), Contracts::DaoStandIn.into())
+        .await?
+        .assert_failure();
+    testenv
+        .patch(registry.id(), b"not wasm".to_vec())
+        .await?
+        .assert_failure();
+
+    testenv
+        .root
+        .call(registry.id(), "patch_contract")
+        .args_json(json!({
+            "contract_bytes": data_url(&DAO_STAND_IN),
+            "terminal": true,
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    let current = registry
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_2");
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirror_copies_versions_from_upstream() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let upstream = &testenv.registry(Contracts::Bootloader).await?;
+    testenv
+        .patch(upstream.id(), Contracts::Factory.into())
+        .await?
+        .assert_success();
+    let mirror = &testenv
+        .deploy_and_init_subaccount(&REGISTRY, &root.id().subaccount("mirror"), root)
+        .await?;
+    root.call(mirror.id(), "set_upstream")
+        .args_json(json!({ "upstream": upstream.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    root.call(mirror.id(), "mirror")
+        .args_json(json!({ "version": "0_0_2" }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_success();
+    root.call(mirror.id(), "mirror")
+        .args_json(json!({ "version": "0_0_1" }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .assert_failure();
+
+    let current = mirror
+        .view("current_version", vec![])
+        .await?
+        .json::<String>()?;
+    assert_eq!(current, "v0_0_2");
+    let fetched = mirror.view("fetch", vec![]).await?.result;
+    assert_eq!(fetched, FACTORY.to_vec());
+    Ok(())
+}
+
+#[tokio::test]
+async fn mirror_refunds_deposit_when_hash_does_not_match() -> anyhow::Result<()> {
+    let testenv = &TestEnv::init().await?;
+    let root = &testenv.root;
+    let upstream = &testenv.registry(Contracts::Bootloader).await?;
+    let mirror = &testenv
+        .deploy_and_init_subaccount(&REGISTRY, &root.id().subaccount("mirror"), root)
+        .await?;
+    root.call(mirror.id(), "set_upstream")
+        .args_json(json!({ "upstream": upstream.id() }))
+        .deposit(1)
+        .transact()
+        .await?
+        .assert_success();
+
+    let balance = root.view_account().await?.balance;
+    let mirrored = root
+        .call(mirror.id(), "mirror")
+        .args_json(json!({
+            "version": "0_0_1",
+            "code_hash": near_primitives::hash::hash(&FACTORY).to_string(),
+        }))
+        .deposit(near!("5 N"))
+        .max_gas()
+        .transact()
+        .await?
+        .json::<bool>()?;
+    assert!(!mirrored);
+    let spent = balance - root.view_account().await?.balance;
+    assert!(spent < near!("0.1 N"), "Deposit was kept: spent {spent}");
+    mirror
+        .view("fetch", vec![])
+        .await
+        .expect_err("nothing was mirrored");
+    Ok(())
+}
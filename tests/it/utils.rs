@@ -6,7 +6,7 @@ use workspaces::network::Sandbox;
 use workspaces::operations::Function;
 use workspaces::result::ExecutionFinalResult;
 use workspaces::{
-    types::{KeyType, PublicKey, SecretKey},
+    types::{CryptoHash, KeyType, PublicKey, SecretKey},
     Account, AccountId, Contract, Worker,
 };
 
@@ -21,13 +21,23 @@ lazy_static_include::lazy_static_include_bytes! {
   pub NEAR_WASM => "./target/res/near.wasm",
   pub FACTORY => "./target/res/factory.wasm",
   pub BOOTLOADER_LOCKED => "./target/res/bootloader_locked.wasm",
+  pub BOOTLOADER_TIMELOCK => "./target/res/bootloader_timelock.wasm",
+  pub BOOTLOADER_MULTISIG => "./target/res/bootloader_multisig.wasm",
+  pub BOOTLOADER_DAO => "./target/res/bootloader_dao.wasm",
+  pub DAO_STAND_IN => "./target/res/dao_stand_in.wasm",
+  pub REGISTRY_ADMINS => "./target/res/registry_admins.wasm",
 
 }
 
 pub enum Contracts {
     Bootloader,
     BootloaderLocked,
+    BootloaderTimelock,
+    BootloaderMultisig,
+    BootloaderDao,
+    DaoStandIn,
     Registry,
+    RegistryAdmins,
     Factory,
     NearRoot,
     Launcher,
@@ -38,7 +48,12 @@ impl From<Contracts> for Vec<u8> {
         match value {
             Contracts::Bootloader => BOOTLOADER.to_vec(),
             Contracts::BootloaderLocked => BOOTLOADER_LOCKED.to_vec(),
+            Contracts::BootloaderTimelock => BOOTLOADER_TIMELOCK.to_vec(),
+            Contracts::BootloaderMultisig => BOOTLOADER_MULTISIG.to_vec(),
+            Contracts::BootloaderDao => BOOTLOADER_DAO.to_vec(),
+            Contracts::DaoStandIn => DAO_STAND_IN.to_vec(),
             Contracts::Registry => REGISTRY.to_vec(),
+            Contracts::RegistryAdmins => REGISTRY_ADMINS.to_vec(),
             Contracts::Factory => FACTORY.to_vec(),
             Contracts::NearRoot => NEAR_WASM.to_vec(),
             Contracts::Launcher => LAUNCHER.to_vec(),
@@ -46,8 +61,12 @@ impl From<Contracts> for Vec<u8> {
     }
 }
 
+/// Contract bytes as a base64 data url, as taken by `DataUrl` arguments
+pub fn data_url(bytes: &[u8]) -> String {
+    format!("data:application/wasm;base64,{}", base64::encode(bytes))
+}
+
 pub const ALICE: &str = "alice";
-#[allow(dead_code)]
 pub const BOB: &str = "bob";
 
 pub type WsResult<T> = Result<T, workspaces::error::Error>;
@@ -183,6 +202,29 @@ impl TestEnv {
             .await
     }
 
+    pub async fn redeploy_with_code_hash(
+        &self,
+        owner: &Account,
+        contract: &Contract,
+        registry: &Contract,
+        code_hash: &CryptoHash,
+    ) -> WsResult<ExecutionFinalResult> {
+        let version = registry
+            .view("current_version", vec![])
+            .await?
+            .json::<String>()?;
+        owner
+            .call(contract.id(), "redeploy")
+            .args_json(json!({
+                "account_id": format!("{}.{}", version, registry.id()),
+                "code_hash": code_hash.to_string(),
+            }))
+            .deposit(1)
+            .max_gas()
+            .transact()
+            .await
+    }
+
     pub async fn deploy_and_init_subaccount(
         &self,
         bytes: &[u8],